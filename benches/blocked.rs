@@ -0,0 +1,43 @@
+//! Insert and lookup throughput of a blocked Bloom filter compared to a classic one.
+//!
+//! Run with `cargo bench --bench blocked`.
+
+use bloom::{BlockedBloomFilter, BloomFilter};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const NUM_ELEMENTS: usize = 10_000_000;
+const FALSE_POSITIVE_RATE: f32 = 0.01;
+
+fn main() {
+    let mut bloom = BloomFilter::new(NUM_ELEMENTS, FALSE_POSITIVE_RATE);
+    let insert = time(|i| bloom.insert(&i));
+    let contains = time(|i| {
+        black_box(bloom.contains(&i));
+    });
+    report("BloomFilter", insert, contains);
+
+    let mut blocked = BlockedBloomFilter::new(NUM_ELEMENTS, FALSE_POSITIVE_RATE);
+    let insert = time(|i| blocked.insert(&i));
+    let contains = time(|i| {
+        black_box(blocked.contains(&i));
+    });
+    report("BlockedBloomFilter", insert, contains);
+}
+
+fn time(mut op: impl FnMut(usize)) -> Duration {
+    let start = Instant::now();
+    for i in 0..NUM_ELEMENTS {
+        op(i);
+    }
+    start.elapsed()
+}
+
+fn report(name: &str, insert: Duration, contains: Duration) {
+    println!(
+        "{}: insert {:.1} ns/op, contains {:.1} ns/op",
+        name,
+        insert.as_nanos() as f64 / NUM_ELEMENTS as f64,
+        contains.as_nanos() as f64 / NUM_ELEMENTS as f64,
+    );
+}
@@ -0,0 +1,67 @@
+//! Insert and lookup throughput benchmark.
+//!
+//! Run with `cargo bench --bench insert`.
+
+use bloom::{BloomFilter, DefaultHashBuilder, Sip128HashBuilder};
+use std::hash::BuildHasher;
+use std::hint::black_box;
+use std::time::Instant;
+
+const NUM_ELEMENTS: usize = 1_000_000;
+
+fn main() {
+    for false_positive_rate in [0.01, 0.0001] {
+        let mut bloom = BloomFilter::new(NUM_ELEMENTS, false_positive_rate);
+
+        let start = Instant::now();
+        for i in 0..NUM_ELEMENTS {
+            bloom.insert(&i);
+        }
+        let insert = start.elapsed();
+
+        let start = Instant::now();
+        for i in 0..NUM_ELEMENTS {
+            black_box(bloom.contains(&i));
+        }
+        let contains = start.elapsed();
+
+        println!(
+            "f={} k={}: insert {:.1} ns/op, contains {:.1} ns/op",
+            false_positive_rate,
+            bloom.num_hash_functions(),
+            insert.as_nanos() as f64 / NUM_ELEMENTS as f64,
+            contains.as_nanos() as f64 / NUM_ELEMENTS as f64,
+        );
+    }
+
+    // Longer items, for which hashing each item once rather than twice pays off most
+    let items: Vec<String> = (0..NUM_ELEMENTS).map(|i| format!("{:064}", i)).collect();
+    bench_hasher("sip", &items, DefaultHashBuilder::default());
+    bench_hasher("sip128", &items, Sip128HashBuilder::default());
+}
+
+fn bench_hasher<S: BuildHasher>(name: &str, items: &[String], hash_builder: S)
+where
+    S::Hasher: 'static,
+{
+    let mut bloom = BloomFilter::with_hasher(NUM_ELEMENTS, 0.01, hash_builder);
+
+    let start = Instant::now();
+    for item in items {
+        bloom.insert(item);
+    }
+    let insert = start.elapsed();
+
+    let start = Instant::now();
+    for item in items {
+        black_box(bloom.contains(item));
+    }
+    let contains = start.elapsed();
+
+    println!(
+        "{} hasher, 64-byte items: insert {:.1} ns/op, contains {:.1} ns/op",
+        name,
+        insert.as_nanos() as f64 / items.len() as f64,
+        contains.as_nanos() as f64 / items.len() as f64,
+    );
+}
@@ -0,0 +1,40 @@
+//! Bulk insertion with `par_insert_all` against `insert_all`.
+//!
+//! Run with `cargo bench --bench parallel --features rayon`.
+
+use bloom::BloomFilter;
+use std::time::Instant;
+
+const NUM_ELEMENTS: usize = 10_000_000;
+const FALSE_POSITIVE_RATE: f32 = 0.01;
+
+fn main() {
+    let numbers: Vec<usize> = (0..NUM_ELEMENTS).collect();
+    bench("integers", &numbers);
+
+    // Longer items, slower to hash, which parallelism pays off most for
+    let strings: Vec<String> = (0..NUM_ELEMENTS).map(|i| format!("{:064}", i)).collect();
+    bench("64-byte strings", &strings);
+}
+
+fn bench<T: std::hash::Hash + Sync>(name: &str, items: &[T]) {
+    let mut sequential = BloomFilter::new(NUM_ELEMENTS, FALSE_POSITIVE_RATE);
+    let start = Instant::now();
+    sequential.insert_all(items);
+    let sequential_time = start.elapsed();
+
+    let mut parallel = BloomFilter::new(NUM_ELEMENTS, FALSE_POSITIVE_RATE);
+    let start = Instant::now();
+    parallel.par_insert_all(items);
+    let parallel_time = start.elapsed();
+
+    assert!(parallel == sequential);
+    println!(
+        "{} {}: insert_all {:.1} ns/op, par_insert_all {:.1} ns/op on {} threads",
+        NUM_ELEMENTS,
+        name,
+        sequential_time.as_nanos() as f64 / NUM_ELEMENTS as f64,
+        parallel_time.as_nanos() as f64 / NUM_ELEMENTS as f64,
+        rayon::current_num_threads(),
+    );
+}
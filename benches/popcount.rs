@@ -0,0 +1,48 @@
+//! Counting the set bits of a large Bloom filter, as `fill_ratio` and `estimate_count` do,
+//! against counting them one by one and byte by byte.
+//!
+//! Run with `cargo bench --bench popcount`.
+
+use bitvec::prelude::*;
+use bloom::BloomFilter;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const NUM_ELEMENTS: usize = 10_000_000;
+const FALSE_POSITIVE_RATE: f32 = 0.01;
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    let mut bloom = BloomFilter::new(NUM_ELEMENTS, FALSE_POSITIVE_RATE);
+    bloom.extend(0..NUM_ELEMENTS / 2);
+    let bits = BitSlice::<u8, Lsb0>::from_slice(bloom.as_raw_slice());
+
+    let fill_ratio = time(|| {
+        black_box(bloom.fill_ratio());
+    });
+    let bytes = time(|| {
+        black_box(bits.count_ones());
+    });
+    let naive = time(|| {
+        black_box(bits.iter().filter(|bit| **bit).count());
+    });
+    println!(
+        "{} bits: fill_ratio {:.2} ms, byte by byte {:.2} ms, bit by bit {:.2} ms",
+        bloom.num_bits(),
+        as_ms(fill_ratio),
+        as_ms(bytes),
+        as_ms(naive),
+    );
+}
+
+fn time(mut op: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        op();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn as_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000.0
+}
@@ -0,0 +1,60 @@
+//! Whole-filter operations on Bloom filters storing their bits in words of different widths.
+//!
+//! Run with `cargo bench --bench storage`.
+
+use bitvec::mem::BitRegister;
+use bitvec::store::BitStore;
+use bloom::{BloomFilter, DefaultHashBuilder};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const NUM_ELEMENTS: usize = 10_000_000;
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    bench::<u8>("u8");
+    bench::<u16>("u16");
+    bench::<u32>("u32");
+    bench::<u64>("u64");
+}
+
+fn bench<W: BitRegister + BitStore>(name: &str) {
+    let mut bloom = filter::<W>();
+    bloom.extend(0..NUM_ELEMENTS / 2);
+    let other = filter::<W>();
+
+    let count = time(|| {
+        black_box(bloom.estimate_count());
+    });
+    let union = time(|| bloom.union(&other).unwrap());
+    let clear = time(|| bloom.clear());
+    println!(
+        "{}: estimate_count {:.2} ms, union {:.2} ms, clear {:.2} ms",
+        name,
+        as_ms(count),
+        as_ms(union),
+        as_ms(clear),
+    );
+}
+
+fn filter<W: BitRegister + BitStore>() -> BloomFilter<DefaultHashBuilder, W> {
+    BloomFilter::builder()
+        .expected_items(NUM_ELEMENTS)
+        .false_positive_rate(FALSE_POSITIVE_RATE)
+        .storage::<W>()
+        .build()
+        .unwrap()
+}
+
+fn time(mut op: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        op();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn as_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000.0
+}
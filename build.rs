@@ -1,4 +1,9 @@
 fn main() {
+    // Only the server needs the generated gRPC code
+    if std::env::var_os("CARGO_FEATURE_SERVER").is_none() {
+        return;
+    }
+
     tonic_build::compile_protos("proto/bloomd.proto")
         .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
 }
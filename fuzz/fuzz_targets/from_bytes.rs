@@ -0,0 +1,19 @@
+//! Feeds arbitrary bytes to `BloomFilter::from_bytes`, which must return an error rather than
+//! panic on anything that isn't a filter, and must only accept inputs it writes back
+//! byte for byte.
+//!
+//! Run with `cargo +nightly fuzz run from_bytes` from the repository root.
+
+#![no_main]
+
+use bloom::BloomFilter;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(bloom) = BloomFilter::from_bytes(data) {
+        // The bit array is exactly as long as the input claims, so this is bounded by it
+        let bytes = bloom.to_bytes();
+        assert!(bytes.len() <= data.len() + 1 + 8 + 16);
+        assert_eq!(BloomFilter::from_bytes(&bytes).unwrap(), bloom);
+    }
+});
@@ -0,0 +1,26 @@
+//! Feeds arbitrary bytes to `BloomFilter::from_bytes_compressed`, which must return an error
+//! rather than panic or abort on anything that isn't a compressed filter, and must only
+//! accept inputs whose filter it writes back unchanged.
+//!
+//! Run with `cargo +nightly fuzz run from_bytes_compressed` from the repository root.
+
+#![no_main]
+
+use bloom::BloomFilter;
+use libfuzzer_sys::fuzz_target;
+
+// Largest filter decompressed, in bits. A few bytes of zero runs legitimately expand to a
+// filter of any size, which would trip the fuzzer's memory limit rather than find a bug.
+const MAX_NUM_BITS: u64 = 1 << 30;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(m) = data.get(8..16) {
+        if u64::from_le_bytes(m.try_into().unwrap()) > MAX_NUM_BITS {
+            return;
+        }
+    }
+    if let Ok(bloom) = BloomFilter::from_bytes_compressed(data) {
+        let bytes = bloom.to_bytes_compressed();
+        assert_eq!(BloomFilter::from_bytes_compressed(&bytes).unwrap(), bloom);
+    }
+});
@@ -0,0 +1,260 @@
+//! Command-line tool building Bloom filter files and querying them.
+
+use bloom::BloomFilter;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: bloom-cli build --output <FILE> [OPTIONS] < ITEMS
+       bloom-cli query <FILE> <ITEM>...
+
+Commands:
+  build   Insert the newline-delimited items read from stdin into a new filter, and write it
+          to FILE
+  query   Print whether the filter in FILE contains each ITEM, exiting with 1 if any is
+          absent
+
+Build options:
+  --output <FILE>         File to write the filter to
+  --num-elements <N>      Expected number of elements [default: 100000]
+  --fp-rate <F>           Target false positive rate, in (0, 1) [default: 0.01]
+
+  -h, --help              Print this help";
+
+/// Subcommand parsed from command-line arguments.
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    Build {
+        output: PathBuf,
+        num_elements: usize,
+        fp_rate: f32,
+    },
+    Query {
+        path: PathBuf,
+        items: Vec<String>,
+    },
+}
+
+/// Outcome of parsing command-line arguments that doesn't yield a [`Command`].
+#[derive(Debug, PartialEq)]
+enum CliError {
+    /// `--help` was requested.
+    Help,
+
+    /// The arguments are invalid.
+    Invalid(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Help => write!(f, "{}", USAGE),
+            CliError::Invalid(msg) => write!(f, "{}\n\n{}", msg, USAGE),
+        }
+    }
+}
+
+impl Command {
+    /// Parse a [`Command`] from command-line arguments, excluding the program name. Options
+    /// take their value either as the next argument or after an `=`.
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, CliError> {
+        let mut args = args.into_iter();
+        match args.next().as_deref() {
+            Some("build") => Command::parse_build(args),
+            Some("query") => Command::parse_query(args),
+            Some("-h" | "--help") => Err(CliError::Help),
+            Some(command) => Err(CliError::Invalid(format!("unknown command {}", command))),
+            None => Err(CliError::Invalid("missing command".to_string())),
+        }
+    }
+
+    fn parse_build<I: Iterator<Item = String>>(mut args: I) -> Result<Command, CliError> {
+        let mut output = None;
+        let mut num_elements = 100_000;
+        let mut fp_rate = 0.01;
+
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| CliError::Invalid(format!("missing value for {}", name)))
+            };
+
+            match name.as_str() {
+                "-h" | "--help" => return Err(CliError::Help),
+                "--output" => output = Some(PathBuf::from(value()?)),
+                "--num-elements" => num_elements = parse_value(&name, &value()?)?,
+                "--fp-rate" => fp_rate = parse_value(&name, &value()?)?,
+                _ => return Err(CliError::Invalid(format!("unknown option {}", name))),
+            }
+        }
+
+        let output = output.ok_or_else(|| CliError::Invalid("missing --output".to_string()))?;
+        if !(fp_rate > 0.0 && fp_rate < 1.0) {
+            return Err(CliError::Invalid(format!(
+                "--fp-rate must be in (0, 1), got {}",
+                fp_rate
+            )));
+        }
+        Ok(Command::Build {
+            output,
+            num_elements,
+            fp_rate,
+        })
+    }
+
+    fn parse_query<I: Iterator<Item = String>>(args: I) -> Result<Command, CliError> {
+        let mut path = None;
+        let mut items = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "-h" | "--help" => return Err(CliError::Help),
+                _ if path.is_none() => path = Some(PathBuf::from(arg)),
+                _ => items.push(arg),
+            }
+        }
+
+        let path = path.ok_or_else(|| CliError::Invalid("missing filter file".to_string()))?;
+        Ok(Command::Query { path, items })
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, CliError> {
+    value
+        .parse()
+        .map_err(|_| CliError::Invalid(format!("invalid value for {}: {}", name, value)))
+}
+
+/// Run a command, returning whether every queried item was found.
+fn run(command: Command) -> Result<bool, Box<dyn Error>> {
+    match command {
+        Command::Build {
+            output,
+            num_elements,
+            fp_rate,
+        } => {
+            let mut bloom = BloomFilter::new(num_elements, fp_rate);
+            let inserted = bloom.insert_lines(io::stdin().lock())?;
+            fs::write(&output, bloom.to_bytes())?;
+            eprintln!(
+                "Inserted {} items into {} (num_bits={} num_hash_functions={})",
+                inserted,
+                output.display(),
+                bloom.num_bits(),
+                bloom.num_hash_functions()
+            );
+            Ok(true)
+        }
+        Command::Query { path, items } => {
+            let bloom = BloomFilter::from_bytes(&fs::read(&path)?)?;
+            let mut found_all = true;
+            for item in &items {
+                let found = bloom.contains(item.as_str());
+                println!("{}\t{}", item, found);
+                found_all &= found;
+            }
+            Ok(found_all)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let command = match Command::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(CliError::Help) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(command) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn parse(args: &[&str]) -> Result<Command, CliError> {
+        Command::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn build() {
+        assert_eq!(
+            parse(&["build", "--output", "words.bloom"]).unwrap(),
+            Command::Build {
+                output: PathBuf::from("words.bloom"),
+                num_elements: 100_000,
+                fp_rate: 0.01,
+            }
+        );
+        assert_eq!(
+            parse(&[
+                "build",
+                "--num-elements=1000",
+                "--fp-rate",
+                "0.001",
+                "--output=words.bloom"
+            ])
+            .unwrap(),
+            Command::Build {
+                output: PathBuf::from("words.bloom"),
+                num_elements: 1_000,
+                fp_rate: 0.001,
+            }
+        );
+    }
+
+    #[test]
+    fn query() {
+        assert_eq!(
+            parse(&["query", "words.bloom", "hi", "--yo"]).unwrap(),
+            Command::Query {
+                path: PathBuf::from("words.bloom"),
+                items: vec!["hi".to_string(), "--yo".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        for args in [
+            &[][..],
+            &["merge"],
+            &["build"],
+            &["build", "--output"],
+            &["build", "--output=a", "--fp-rate=1"],
+            &["build", "--output=a", "--num-elements=lots"],
+            &["build", "--output=a", "--verbose"],
+            &["query"],
+        ] {
+            assert!(
+                matches!(parse(args), Err(CliError::Invalid(_))),
+                "{:?}",
+                args
+            );
+        }
+        assert_eq!(parse(&["--help"]), Err(CliError::Help));
+        assert_eq!(parse(&["build", "-h"]), Err(CliError::Help));
+    }
+}
@@ -0,0 +1,215 @@
+//! Command-line configuration of the bloomd server.
+
+use crate::hasher::ItemHasher;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: bloomd-server [OPTIONS]
+
+Options:
+  --num-elements <N>          Expected number of elements [default: 100000]
+  --false-positive-rate <F>   Target false positive rate, in (0, 1) [default: 0.01]
+  --bind-addr <ADDR>          Address to listen on [default: [::1]:50051]
+  --persist-path <FILE>       Load the filter from FILE on startup, if it exists, and save it
+                              there on shutdown
+  --reject-when-saturated     Fail inserts into a filter holding more items than it was sized
+                              for with RESOURCE_EXHAUSTED, rather than letting its false
+                              positive rate climb
+  --max-item-bytes <N>        Fail inserts and lookups of items longer than N bytes with
+                              INVALID_ARGUMENT [default: unlimited]
+  --max-filter-bytes <N>      Fail requests to create filters that would take more than N
+                              bytes with INVALID_ARGUMENT [default: 1073741824]
+  --hasher <HASHER>           Hash items with sip, resistant to adversarial items, or fnv,
+                              faster but not; filters persisted under one can't be loaded
+                              under the other [default: sip]
+  -h, --help                  Print this help";
+
+/// Server configuration parsed from command-line arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub num_elements: usize,
+    pub false_positive_rate: f32,
+    pub bind_addr: SocketAddr,
+    pub persist_path: Option<PathBuf>,
+    pub reject_when_saturated: bool,
+    pub max_item_bytes: Option<usize>,
+    pub max_filter_bytes: usize,
+    pub hasher: ItemHasher,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            num_elements: 100_000,
+            false_positive_rate: 0.01,
+            bind_addr: "[::1]:50051".parse().unwrap(),
+            persist_path: None,
+            reject_when_saturated: false,
+            max_item_bytes: None,
+            max_filter_bytes: 1 << 30,
+            hasher: ItemHasher::Sip,
+        }
+    }
+}
+
+/// Outcome of parsing command-line arguments that doesn't yield a [`Config`].
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    /// `--help` was requested.
+    Help,
+
+    /// The arguments are invalid.
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Help => write!(f, "{}", USAGE),
+            ConfigError::Invalid(msg) => write!(f, "{}\n\n{}", msg, USAGE),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Parse a [`Config`] from command-line arguments, excluding the program name. Options
+    /// take their value either as the next argument or after an `=`.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| ConfigError::Invalid(format!("missing value for {}", name)))
+            };
+
+            match name.as_str() {
+                "-h" | "--help" => return Err(ConfigError::Help),
+                "--num-elements" => config.num_elements = parse_value(&name, &value()?)?,
+                "--false-positive-rate" => {
+                    config.false_positive_rate = parse_value(&name, &value()?)?
+                }
+                "--bind-addr" => config.bind_addr = parse_value(&name, &value()?)?,
+                "--persist-path" => config.persist_path = Some(value()?.into()),
+                "--reject-when-saturated" => {
+                    if inline_value.is_some() {
+                        return Err(ConfigError::Invalid(format!("{} takes no value", name)));
+                    }
+                    config.reject_when_saturated = true
+                }
+                "--max-item-bytes" => config.max_item_bytes = Some(parse_value(&name, &value()?)?),
+                "--max-filter-bytes" => config.max_filter_bytes = parse_value(&name, &value()?)?,
+                "--hasher" => config.hasher = parse_value(&name, &value()?)?,
+                _ => return Err(ConfigError::Invalid(format!("unknown option {}", name))),
+            }
+        }
+
+        if !(config.false_positive_rate > 0.0 && config.false_positive_rate < 1.0) {
+            return Err(ConfigError::Invalid(format!(
+                "--false-positive-rate must be in (0, 1), got {}",
+                config.false_positive_rate
+            )));
+        }
+        Ok(config)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, ConfigError> {
+    value
+        .parse()
+        .map_err(|_| ConfigError::Invalid(format!("invalid value for {}: {}", name, value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::*;
+
+    fn parse(args: &[&str]) -> Result<Config, ConfigError> {
+        Config::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(parse(&[]).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn options() {
+        let config = parse(&[
+            "--num-elements",
+            "1000",
+            "--false-positive-rate=0.05",
+            "--bind-addr",
+            "127.0.0.1:8080",
+            "--persist-path",
+            "/var/lib/bloomd/filter",
+            "--reject-when-saturated",
+            "--max-item-bytes=256",
+            "--max-filter-bytes=4096",
+            "--hasher",
+            "fnv",
+        ])
+        .unwrap();
+        assert_eq!(config.num_elements, 1_000);
+        assert_eq!(config.false_positive_rate, 0.05);
+        assert_eq!(config.bind_addr, "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(
+            config.persist_path,
+            Some(PathBuf::from("/var/lib/bloomd/filter"))
+        );
+        assert!(config.reject_when_saturated);
+        assert_eq!(config.max_item_bytes, Some(256));
+        assert_eq!(config.max_filter_bytes, 4_096);
+        assert_eq!(config.hasher, ItemHasher::Fnv);
+    }
+
+    #[test]
+    fn invalid_false_positive_rate() {
+        for rate in ["0", "1", "1.5", "-0.1", "NaN"] {
+            assert!(matches!(
+                parse(&["--false-positive-rate", rate]),
+                Err(ConfigError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(matches!(
+            parse(&["--num-elements"]),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse(&["--num-elements", "lots"]),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse(&["--reject-when-saturated=yes"]),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse(&["--max-item-bytes", "-1"]),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse(&["--hasher=ahash"]),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            parse(&["--verbose"]),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert_eq!(parse(&["--help"]), Err(ConfigError::Help));
+    }
+}
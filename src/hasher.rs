@@ -0,0 +1,152 @@
+//! Hashers the bloomd server can hash items with, picked at startup with `--hasher`.
+
+use bloom::DefaultHashBuilder;
+use fnv::FnvHasher;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+
+/// Builds the hashers applied to the items of every filter.
+///
+/// The choice is made at runtime, so the hashers dispatch on it with a `match` rather than
+/// the filters being monomorphized over each.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ItemHasher {
+    /// SipHash with fixed keys, the library's default, which hashes items like a
+    /// [`bloom::BloomFilter::new`].
+    #[default]
+    Sip,
+
+    /// 64-bit FNV-1a, several times faster on short items, but trivial for an adversary to
+    /// pick colliding items for.
+    Fnv,
+}
+
+impl ItemHasher {
+    /// Returns the name of the hasher, as passed to `--hasher`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ItemHasher::Sip => "sip",
+            ItemHasher::Fnv => "fnv",
+        }
+    }
+
+    /// Returns the byte identifying the hasher in persisted filters.
+    pub fn id(self) -> u8 {
+        match self {
+            ItemHasher::Sip => 0,
+            ItemHasher::Fnv => 1,
+        }
+    }
+
+    /// Returns the hasher identified by `id` in persisted filters, if any.
+    pub fn from_id(id: u8) -> Option<ItemHasher> {
+        [ItemHasher::Sip, ItemHasher::Fnv]
+            .into_iter()
+            .find(|hasher| hasher.id() == id)
+    }
+}
+
+impl fmt::Display for ItemHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ItemHasher {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<ItemHasher, ()> {
+        match s {
+            "sip" => Ok(ItemHasher::Sip),
+            "fnv" => Ok(ItemHasher::Fnv),
+            _ => Err(()),
+        }
+    }
+}
+
+impl BuildHasher for ItemHasher {
+    type Hasher = ItemHasherState;
+
+    fn build_hasher(&self) -> ItemHasherState {
+        match self {
+            ItemHasher::Sip => ItemHasherState::Sip(DefaultHashBuilder::default().build_hasher()),
+            ItemHasher::Fnv => ItemHasherState::Fnv(FnvHasher::default()),
+        }
+    }
+}
+
+/// Hasher built by an [`ItemHasher`].
+///
+/// Every method is forwarded, as SipHash hashes integers differently from their bytes.
+pub enum ItemHasherState {
+    Sip(<DefaultHashBuilder as BuildHasher>::Hasher),
+    Fnv(FnvHasher),
+}
+
+macro_rules! forward {
+    ($($method:ident($ty:ty)),*) => {
+        $(
+            fn $method(&mut self, i: $ty) {
+                match self {
+                    ItemHasherState::Sip(hasher) => hasher.$method(i),
+                    ItemHasherState::Fnv(hasher) => hasher.$method(i),
+                }
+            }
+        )*
+    };
+}
+
+impl Hasher for ItemHasherState {
+    fn finish(&self) -> u64 {
+        match self {
+            ItemHasherState::Sip(hasher) => hasher.finish(),
+            ItemHasherState::Fnv(hasher) => hasher.finish(),
+        }
+    }
+
+    forward!(
+        write(&[u8]),
+        write_u8(u8),
+        write_u16(u16),
+        write_u32(u32),
+        write_u64(u64),
+        write_usize(usize)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hasher::*;
+    use bloom::AtomicBloomFilter;
+
+    #[test]
+    fn sip_matches_default() {
+        let bloom = AtomicBloomFilter::new(1_000, 0.01);
+        let sip = AtomicBloomFilter::with_hasher(1_000, 0.01, ItemHasher::Sip);
+        let fnv = AtomicBloomFilter::with_hasher(1_000, 0.01, ItemHasher::Fnv);
+        for item in ["hi", "yo"] {
+            bloom.insert(item);
+            sip.insert(item);
+            fnv.insert(item);
+        }
+        bloom.insert(&42_u64);
+        sip.insert(&42_u64);
+        fnv.insert(&42_u64);
+
+        let bytes = bloom.to_bloom_filter().to_bytes();
+        assert_eq!(sip.to_bloom_filter().to_bytes(), bytes);
+        assert_ne!(fnv.to_bloom_filter().to_bytes(), bytes);
+        assert!(fnv.contains("hi") && fnv.contains(&42_u64));
+    }
+
+    #[test]
+    fn names_and_ids() {
+        for hasher in [ItemHasher::Sip, ItemHasher::Fnv] {
+            assert_eq!(hasher.name().parse(), Ok(hasher));
+            assert_eq!(ItemHasher::from_id(hasher.id()), Some(hasher));
+        }
+        assert_eq!("ahash".parse::<ItemHasher>(), Err(()));
+        assert_eq!(ItemHasher::from_id(2), None);
+    }
+}
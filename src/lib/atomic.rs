@@ -0,0 +1,391 @@
+//! A Bloom filter backed by atomic words, which can be inserted into concurrently.
+
+use crate::{
+    calc_index, calc_index_enhanced, calc_sizing, hash_pair, BloomFilter, DefaultHashBuilder,
+};
+use alloc::vec::Vec;
+use bitvec::prelude::*;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Bloom filter data structure whose bits can be set through a shared reference.
+///
+/// The bits are stored in [`AtomicU64`] words: [`AtomicBloomFilter::insert`] sets each bit
+/// with an atomic `fetch_or` and [`AtomicBloomFilter::contains`] reads them with atomic
+/// loads, so any number of threads can insert and query at once without a lock. An item is
+/// guaranteed to be found by a lookup that happens after its insert returns.
+///
+/// Items are hashed exactly as in a [`BloomFilter`] with the same parameters, and the two
+/// convert into each other with [`From`] and [`AtomicBloomFilter::to_bloom_filter`], e.g.
+/// to serialize the filter.
+///
+/// [`BloomFilter`]: crate::BloomFilter
+pub struct AtomicBloomFilter<S = DefaultHashBuilder> {
+    // Number of hash functions
+    num_hash_functions: usize,
+
+    // Size in bits of the filter
+    num_bits: usize,
+
+    // Bits of the filter, bit `i` being bit `i % 64` of word `i / 64`
+    words: Vec<AtomicU64>,
+
+    // Mixed into the hashes of every item
+    seed: u64,
+
+    // Number of items and false positive rate the filter was designed for
+    expected_elements: usize,
+    target_false_positive_rate: f64,
+
+    // Number of inserts that set at least one new bit
+    inserted: AtomicU64,
+
+    // Whether bit indices are derived by enhanced rather than plain double hashing
+    enhanced_double_hashing: bool,
+
+    // Builds the hashers applied to items
+    hash_builder: S,
+}
+
+impl AtomicBloomFilter {
+    /// Instantiate a new [`AtomicBloomFilter`] by providing the expected `num_elements` that
+    /// will be added to the filter and the target `false_positive_rate`.
+    ///
+    /// [`AtomicBloomFilter`]: AtomicBloomFilter
+    pub fn new(num_elements: usize, false_positive_rate: f32) -> AtomicBloomFilter {
+        AtomicBloomFilter::with_hasher(
+            num_elements,
+            false_positive_rate,
+            DefaultHashBuilder::default(),
+        )
+    }
+}
+
+impl<S: BuildHasher> AtomicBloomFilter<S>
+where
+    S::Hasher: 'static,
+{
+    /// Instantiate a new [`AtomicBloomFilter`] like [`AtomicBloomFilter::new`], hashing items
+    /// with hashers built by `hash_builder`.
+    ///
+    /// [`AtomicBloomFilter`]: AtomicBloomFilter
+    pub fn with_hasher(
+        num_elements: usize,
+        false_positive_rate: f32,
+        hash_builder: S,
+    ) -> AtomicBloomFilter<S> {
+        // Sized like a `BloomFilter`, so that the two set the same bits
+        let (m, k) = calc_sizing(num_elements, false_positive_rate as f64);
+
+        AtomicBloomFilter {
+            num_hash_functions: k,
+            num_bits: m,
+            words: (0..m.div_ceil(64)).map(|_| AtomicU64::new(0)).collect(),
+            seed: 0,
+            expected_elements: num_elements,
+            target_false_positive_rate: false_positive_rate as f64,
+            inserted: AtomicU64::new(0),
+            enhanced_double_hashing: false,
+            hash_builder,
+        }
+    }
+
+    /// Returns size in bytes of the filter's bit vector.
+    pub fn size(&self) -> usize {
+        self.num_bits.div_ceil(8)
+    }
+
+    /// Returns the number of hash functions, `k`.
+    pub fn num_hash_functions(&self) -> usize {
+        self.num_hash_functions
+    }
+
+    /// Returns the size in bits of the filter, `m`.
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+
+    /// Returns the number of items the filter was designed to hold at its target false
+    /// positive rate, like [`BloomFilter::capacity`].
+    pub fn capacity(&self) -> usize {
+        self.expected_elements
+    }
+
+    /// Returns whether more items have been inserted than the filter's
+    /// [`capacity`](AtomicBloomFilter::capacity), counted like
+    /// [`BloomFilter::is_saturated`].
+    pub fn is_saturated(&self) -> bool {
+        self.inserted.load(Ordering::Relaxed) > self.expected_elements as u64
+    }
+
+    /// Returns an estimate of the number of distinct items inserted into the filter, like
+    /// [`BloomFilter::estimate_count`].
+    ///
+    /// The words are read one at a time, so while other threads are inserting, the estimate
+    /// is only approximate: it can reflect some of the bits of an in-flight insert but not
+    /// others.
+    pub fn estimate_count(&self) -> usize {
+        let m = self.num_bits as f64;
+        let k = self.num_hash_functions as f64;
+        let x = self.count_ones() as f64;
+        (-(m / k) * (1.0 - x / m).ln()).round() as usize
+    }
+
+    /// Returns the estimated false positive rate of the filter in its current state, like
+    /// [`BloomFilter::current_false_positive_rate`].
+    ///
+    /// Approximate under concurrent inserts, like [`AtomicBloomFilter::estimate_count`].
+    pub fn current_false_positive_rate(&self) -> f64 {
+        self.fill_ratio().powi(self.num_hash_functions as i32)
+    }
+
+    /// Returns the fraction of bits currently set in the filter, between `0.0` and `1.0`.
+    ///
+    /// Approximate under concurrent inserts, like [`AtomicBloomFilter::estimate_count`].
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.num_bits as f64
+    }
+
+    /// Insert an item into the filter. Can be called from several threads at once.
+    pub fn insert<T: Hash + ?Sized>(&self, item: &T) {
+        let (h1, h2) = hash_pair(&self.hash_builder, self.seed, item);
+        let mut flipped = false;
+        for i in 0..self.num_hash_functions {
+            let bit = self.calc_bit(h1, h2, i);
+            let mask = 1 << (bit % 64);
+            flipped |= self.words[bit / 64].fetch_or(mask, Ordering::Relaxed) & mask == 0;
+        }
+        if flipped {
+            self.inserted.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Insert every item of a slice into the filter.
+    pub fn insert_all<T: Hash>(&self, items: &[T]) {
+        for item in items {
+            self.insert(item);
+        }
+    }
+
+    /// Returns whether the filter contains the item. It may return a false positive but will
+    /// never return a false negative.
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        let (h1, h2) = hash_pair(&self.hash_builder, self.seed, item);
+        (0..self.num_hash_functions).all(|i| {
+            let bit = self.calc_bit(h1, h2, i);
+            self.words[bit / 64].load(Ordering::Relaxed) & (1 << (bit % 64)) != 0
+        })
+    }
+
+    /// Reset the filter to its empty state.
+    ///
+    /// The words are cleared one at a time, so items inserted concurrently may be left
+    /// partially set.
+    pub fn clear(&self) {
+        for word in &self.words {
+            word.store(0, Ordering::Relaxed);
+        }
+        self.inserted.store(0, Ordering::Relaxed);
+    }
+
+    /// Copy the filter into a [`BloomFilter`] with the same parameters and bits.
+    ///
+    /// As with [`AtomicBloomFilter::estimate_count`], the copy is only a consistent snapshot
+    /// if no other thread is inserting.
+    ///
+    /// [`BloomFilter`]: crate::BloomFilter
+    pub fn to_bloom_filter(&self) -> BloomFilter<S>
+    where
+        S: Clone,
+    {
+        let mut bits = bitvec![u8, Lsb0; 0; self.num_bits];
+        for (chunk, word) in bits.chunks_mut(64).zip(&self.words) {
+            chunk.store_le(word.load(Ordering::Relaxed));
+        }
+        BloomFilter {
+            num_hash_functions: self.num_hash_functions,
+            bits,
+            seed: self.seed,
+            expected_elements: self.expected_elements,
+            target_false_positive_rate: self.target_false_positive_rate,
+            inserted: self.inserted.load(Ordering::Relaxed),
+            enhanced_double_hashing: self.enhanced_double_hashing,
+            hash_builder: self.hash_builder.clone(),
+            metrics: Default::default(),
+        }
+    }
+
+    /// Calculate index of bit for given item hashes and hashing function number, like a
+    /// [`BloomFilter`] with the same parameters.
+    ///
+    /// [`BloomFilter`]: crate::BloomFilter
+    fn calc_bit(&self, h1: u64, h2: u64, hash_func_num: usize) -> usize {
+        if self.enhanced_double_hashing {
+            calc_index_enhanced(h1, h2, hash_func_num, self.num_bits)
+        } else {
+            calc_index(h1, h2, hash_func_num, self.num_bits)
+        }
+    }
+
+    /// Count the bits set, reading the words one at a time.
+    fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.load(Ordering::Relaxed).count_ones() as usize)
+            .sum()
+    }
+}
+
+/// Converts a [`BloomFilter`] into an [`AtomicBloomFilter`] with the same parameters and
+/// bits, so that it reports the same items.
+///
+/// [`BloomFilter`]: crate::BloomFilter
+/// [`AtomicBloomFilter`]: AtomicBloomFilter
+impl<S> From<BloomFilter<S>> for AtomicBloomFilter<S> {
+    fn from(bloom: BloomFilter<S>) -> Self {
+        AtomicBloomFilter {
+            num_hash_functions: bloom.num_hash_functions,
+            num_bits: bloom.bits.len(),
+            words: bloom
+                .bits
+                .chunks(64)
+                .map(|chunk| AtomicU64::new(chunk.load_le()))
+                .collect(),
+            seed: bloom.seed,
+            expected_elements: bloom.expected_elements,
+            target_false_positive_rate: bloom.target_false_positive_rate,
+            inserted: AtomicU64::new(bloom.inserted),
+            enhanced_double_hashing: bloom.enhanced_double_hashing,
+            hash_builder: bloom.hash_builder,
+        }
+    }
+}
+
+/// Summarizes the filter's parameters rather than dumping its bits, like [`BloomFilter`].
+///
+/// [`BloomFilter`]: crate::BloomFilter
+impl<S> fmt::Debug for AtomicBloomFilter<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicBloomFilter")
+            .field("num_hash_functions", &self.num_hash_functions)
+            .field("num_bits", &self.num_bits)
+            .field("seed", &self.seed)
+            .field("size", &self.num_bits.div_ceil(8))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atomic::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn insert_contains() {
+        let bloom = AtomicBloomFilter::new(1_000, 0.01);
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+        assert!(!bloom.contains(&"yo"));
+        bloom.clear();
+        assert!(!bloom.contains(&"hi"));
+    }
+
+    #[test]
+    fn saturated() {
+        let bloom = AtomicBloomFilter::new(100, 0.01);
+        assert_eq!(bloom.capacity(), 100);
+        for i in 0..100 {
+            bloom.insert(&i);
+        }
+        assert!(!bloom.is_saturated());
+        bloom.insert_all(&[0, 1, 2]);
+        assert!(!bloom.is_saturated());
+        bloom.insert_all(&[100, 101, 102]);
+        assert!(bloom.is_saturated());
+        assert!(bloom.to_bloom_filter().is_saturated());
+
+        bloom.clear();
+        assert!(!bloom.is_saturated());
+    }
+
+    #[test]
+    fn concurrent_inserts() {
+        const THREADS: usize = 8;
+        const ITEMS_PER_THREAD: usize = 10_000;
+
+        let bloom = Arc::new(AtomicBloomFilter::new(THREADS * ITEMS_PER_THREAD, 0.01));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let bloom = bloom.clone();
+                thread::spawn(move || {
+                    for i in t * ITEMS_PER_THREAD..(t + 1) * ITEMS_PER_THREAD {
+                        bloom.insert(&i);
+                        assert!(bloom.contains(&i));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        for i in 0..THREADS * ITEMS_PER_THREAD {
+            assert!(bloom.contains(&i));
+        }
+        let estimate = bloom.estimate_count() as f64;
+        let expected = (THREADS * ITEMS_PER_THREAD) as f64;
+        assert!((estimate - expected).abs() / expected < 0.02);
+    }
+
+    #[test]
+    fn same_bits_as_bloom_filter() {
+        let mut bloom = BloomFilter::new_with_seed(10_000, 0.01, 42);
+        let atomic = AtomicBloomFilter::from(bloom.clone());
+        for i in 0..1_000 {
+            bloom.insert(&i);
+            atomic.insert(&i);
+        }
+        assert_eq!(atomic.num_bits(), bloom.num_bits());
+        assert_eq!(atomic.num_hash_functions(), bloom.num_hash_functions());
+        assert_eq!(atomic.size(), bloom.size());
+        assert_eq!(atomic.estimate_count(), bloom.estimate_count());
+        assert_eq!(atomic.to_bloom_filter(), bloom);
+
+        let converted = AtomicBloomFilter::from(bloom);
+        assert!((0..1_000).all(|i| converted.contains(&i)));
+    }
+
+    #[test]
+    fn sized_like_bloom_filter() {
+        for (n, f) in [
+            (1, 0.5),
+            (1_000, 0.01),
+            (123_457, 0.001),
+            (10_000_000, 1e-9),
+        ] {
+            let bloom = BloomFilter::new(n, f);
+            let atomic = AtomicBloomFilter::new(n, f);
+            assert_eq!(atomic.num_bits(), bloom.num_bits());
+            assert_eq!(atomic.num_hash_functions(), bloom.num_hash_functions());
+            assert_eq!(atomic.to_bloom_filter(), bloom);
+        }
+    }
+
+    #[test]
+    fn enhanced_double_hashing() {
+        let mut bloom = BloomFilter::builder()
+            .expected_items(10_000)
+            .enhanced_double_hashing(true)
+            .build()
+            .unwrap();
+        let atomic = AtomicBloomFilter::from(bloom.clone());
+        for i in 0..1_000 {
+            bloom.insert(&i);
+            atomic.insert(&i);
+        }
+        assert_eq!(atomic.to_bloom_filter(), bloom);
+        assert!(atomic.to_bloom_filter().enhanced_double_hashing());
+    }
+}
@@ -0,0 +1,400 @@
+use crate::{calc_budget_sizing, calc_sizing, BloomError, BloomFilter, DefaultHashBuilder};
+use bitvec::mem::BitRegister;
+use bitvec::store::BitStore;
+use core::hash::BuildHasher;
+use core::marker::PhantomData;
+
+// False positive rate used when none is set
+const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// Builder for a [`BloomFilter`], as an alternative to the positional arguments of
+/// [`BloomFilter::new`] and [`BloomFilter::with_hasher`].
+///
+/// The expected number of items must be set; the false positive rate defaults to `0.01`, the
+/// hasher to [`DefaultHashBuilder`] and the storage word to `u8`.
+///
+/// ```
+/// use bloom::BloomFilter;
+/// let mut bloom = BloomFilter::builder()
+///     .expected_items(1_000)
+///     .false_positive_rate(0.001)
+///     .build()
+///     .unwrap();
+///
+/// bloom.insert(&"hi");
+/// assert!(bloom.contains(&"hi"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BloomFilterBuilder<S = DefaultHashBuilder, W = u8> {
+    expected_items: Option<usize>,
+    false_positive_rate: Option<f64>,
+    max_bytes: Option<usize>,
+    seed: u64,
+    enhanced_double_hashing: bool,
+    hash_builder: S,
+    storage: PhantomData<W>,
+}
+
+impl BloomFilterBuilder {
+    /// Create a builder with nothing set.
+    pub fn new() -> BloomFilterBuilder {
+        BloomFilterBuilder::default()
+    }
+}
+
+impl<S, W> BloomFilterBuilder<S, W> {
+    /// Set the expected number of items that will be added to the filter.
+    pub fn expected_items(mut self, expected_items: usize) -> Self {
+        self.expected_items = Some(expected_items);
+        self
+    }
+
+    /// Set the target false positive rate, which must be in `(0, 1)`.
+    pub fn false_positive_rate(mut self, false_positive_rate: f64) -> Self {
+        self.false_positive_rate = Some(false_positive_rate);
+        self
+    }
+
+    /// Cap the filter's bit array at `max_bytes` bytes. If a filter meeting the false
+    /// positive rate would be larger, it's built with `max_bytes` bytes instead, relaxing
+    /// the rate to the lowest achievable with them at the expected number of items, as with
+    /// [`BloomFilter::with_memory_budget`]. [`BloomFilterBuilder::build_with_report`] tells
+    /// whether that happened.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Mix `seed` into the hashes of every item, as with [`BloomFilter::new_with_seed`].
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Derive the bit indices of items by Dillinger and Manolios' enhanced double hashing
+    /// rather than plain double hashing, so that an item's hash functions don't collapse
+    /// onto a few bits when its second hash shares a large factor with the filter's size.
+    ///
+    /// That's rare for typical items, whose indices collide about as often under either
+    /// scheme, a little more often under the enhanced one, so it mostly guards against items
+    /// chosen to collide. Check the difference for a sample of items with
+    /// [`BloomFilter::index_collisions`].
+    ///
+    /// Filters set different bits depending on this, so the two kinds can't be merged or
+    /// compared, and builds that predate it can't read filters serialized with it.
+    pub fn enhanced_double_hashing(mut self, enabled: bool) -> Self {
+        self.enhanced_double_hashing = enabled;
+        self
+    }
+
+    /// Hash items with hashers built by `hash_builder`.
+    pub fn hasher<H>(self, hash_builder: H) -> BloomFilterBuilder<H, W> {
+        BloomFilterBuilder {
+            expected_items: self.expected_items,
+            false_positive_rate: self.false_positive_rate,
+            max_bytes: self.max_bytes,
+            seed: self.seed,
+            enhanced_double_hashing: self.enhanced_double_hashing,
+            hash_builder,
+            storage: PhantomData,
+        }
+    }
+
+    /// Store the bits in words of type `V`, one of `u8`, `u16`, `u32`, `u64` or `usize`.
+    pub fn storage<V>(self) -> BloomFilterBuilder<S, V> {
+        BloomFilterBuilder {
+            expected_items: self.expected_items,
+            false_positive_rate: self.false_positive_rate,
+            max_bytes: self.max_bytes,
+            seed: self.seed,
+            enhanced_double_hashing: self.enhanced_double_hashing,
+            hash_builder: self.hash_builder,
+            storage: PhantomData,
+        }
+    }
+}
+
+impl<S: BuildHasher, W: BitRegister + BitStore> BloomFilterBuilder<S, W>
+where
+    S::Hasher: 'static,
+{
+    /// Build the filter.
+    ///
+    /// Returns [`BloomError::InvalidParameter`] if the expected number of items isn't set,
+    /// the false positive rate isn't in `(0, 1)` or the maximum size is zero bytes or too
+    /// many bytes to count in bits.
+    pub fn build(self) -> Result<BloomFilter<S, W>, BloomError> {
+        self.build_with_report().map(|(bloom, _)| bloom)
+    }
+
+    /// Build the filter like [`BloomFilterBuilder::build`], also returning a
+    /// [`BuildReport`] of how it differs from the one requested.
+    ///
+    /// ```
+    /// use bloom::BloomFilter;
+    /// let (bloom, report) = BloomFilter::builder()
+    ///     .expected_items(1_000_000)
+    ///     .false_positive_rate(0.001)
+    ///     .max_bytes(1 << 20)
+    ///     .build_with_report()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(bloom.size(), 1 << 20);
+    /// assert!(report.false_positive_rate > 0.001);
+    /// assert!(report.warning.is_some());
+    /// ```
+    pub fn build_with_report(self) -> Result<(BloomFilter<S, W>, BuildReport), BloomError> {
+        let expected_items = self.expected_items.ok_or_else(|| {
+            BloomError::InvalidParameter("expected_items must be set".to_string())
+        })?;
+        let false_positive_rate = self
+            .false_positive_rate
+            .unwrap_or(DEFAULT_FALSE_POSITIVE_RATE);
+        // Filters' rates are compared as `f32`s, so rates too small for one are rejected too
+        if !(false_positive_rate as f32 > 0.0 && false_positive_rate < 1.0) {
+            return Err(BloomError::InvalidParameter(format!(
+                "false_positive_rate must be in (0, 1), got {}",
+                false_positive_rate
+            )));
+        }
+
+        let budget = match self.max_bytes {
+            Some(0) => {
+                return Err(BloomError::InvalidParameter(
+                    "max_bytes must be greater than zero".to_string(),
+                ))
+            }
+            Some(max_bytes) => {
+                let sizing = calc_budget_sizing(expected_items, max_bytes).ok_or_else(|| {
+                    BloomError::InvalidParameter(format!(
+                        "max_bytes must be at most {}, got {}",
+                        usize::MAX / 8,
+                        max_bytes
+                    ))
+                })?;
+                Some((max_bytes, sizing))
+            }
+            None => None,
+        };
+
+        let (m, k) = calc_sizing(expected_items, false_positive_rate);
+        let (mut bloom, report) = match budget {
+            Some((max_bytes, (budget_m, budget_k, relaxed_rate))) if m.div_ceil(8) > max_bytes => {
+                let bloom = BloomFilter::with_geometry(
+                    budget_m,
+                    budget_k,
+                    expected_items,
+                    relaxed_rate,
+                    self.hash_builder,
+                );
+                let warning = format!(
+                    "a filter for {} items at false positive rate {} takes {} bytes, over \
+                     max_bytes {}; relaxed the rate to {}",
+                    expected_items,
+                    false_positive_rate,
+                    m.div_ceil(8),
+                    max_bytes,
+                    relaxed_rate
+                );
+                let report = BuildReport {
+                    false_positive_rate: relaxed_rate,
+                    warning: Some(warning),
+                };
+                (bloom, report)
+            }
+            _ => {
+                let bloom = BloomFilter::with_geometry(
+                    m,
+                    k,
+                    expected_items,
+                    false_positive_rate,
+                    self.hash_builder,
+                );
+                let report = BuildReport {
+                    false_positive_rate,
+                    warning: None,
+                };
+                (bloom, report)
+            }
+        };
+        bloom.seed = self.seed;
+        bloom.enhanced_double_hashing = self.enhanced_double_hashing;
+        Ok((bloom, report))
+    }
+}
+
+/// How a filter built by [`BloomFilterBuilder::build_with_report`] differs from the one
+/// requested.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildReport {
+    /// The false positive rate the filter is designed for, which is the one requested
+    /// unless it was relaxed to fit in [`BloomFilterBuilder::max_bytes`].
+    pub false_positive_rate: f64,
+
+    /// A description of how the request was relaxed, if it was.
+    pub warning: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::*;
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    fn build() {
+        let bloom = BloomFilterBuilder::new()
+            .expected_items(1_000_000)
+            .false_positive_rate(0.02)
+            .build()
+            .unwrap();
+        assert_eq!(bloom, BloomFilter::new(1_000_000, 0.02));
+    }
+
+    #[test]
+    fn default_false_positive_rate() {
+        let bloom = BloomFilter::builder()
+            .expected_items(100_000)
+            .build()
+            .unwrap();
+        assert_eq!(bloom, BloomFilter::new(100_000, 0.01));
+    }
+
+    #[test]
+    fn seed() {
+        let bloom = BloomFilter::builder()
+            .expected_items(1_000)
+            .seed(42)
+            .build()
+            .unwrap();
+        assert_eq!(bloom, BloomFilter::new_with_seed(1_000, 0.01, 42));
+    }
+
+    #[test]
+    fn enhanced_double_hashing() {
+        let bloom = BloomFilter::builder()
+            .expected_items(1_000)
+            .enhanced_double_hashing(true)
+            .build()
+            .unwrap();
+        assert!(bloom.enhanced_double_hashing());
+        assert_ne!(bloom, BloomFilter::new(1_000, 0.01));
+    }
+
+    #[test]
+    fn hasher() {
+        let mut bloom = BloomFilter::builder()
+            .expected_items(1_000)
+            .hasher(RandomState::new())
+            .build()
+            .unwrap();
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+        assert!(!bloom.contains(&"yo"));
+    }
+
+    #[test]
+    fn storage() {
+        let mut bloom = BloomFilter::builder()
+            .expected_items(1_000)
+            .storage::<u64>()
+            .build()
+            .unwrap();
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+        assert!(!bloom.contains(&"yo"));
+    }
+
+    #[test]
+    fn invalid_false_positive_rate() {
+        for rate in [0.0, -0.1, 1.0, 1.5, f64::NAN, 1e-300] {
+            assert!(matches!(
+                BloomFilter::builder()
+                    .expected_items(1_000)
+                    .false_positive_rate(rate)
+                    .build(),
+                Err(BloomError::InvalidParameter(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn max_bytes_binds() {
+        let (bloom, report) = BloomFilter::builder()
+            .expected_items(10_000)
+            .false_positive_rate(0.001)
+            .max_bytes(4_096)
+            .seed(42)
+            .build_with_report()
+            .unwrap();
+        assert_eq!(bloom.size(), 4_096);
+        assert_eq!(bloom.seed(), 42);
+        assert!(report.false_positive_rate > 0.001);
+        assert_eq!(
+            bloom.target_false_positive_rate(),
+            report.false_positive_rate
+        );
+        let budget = BloomFilter::with_memory_budget(10_000, 4_096);
+        assert_eq!(bloom.num_hash_functions(), budget.num_hash_functions());
+        assert_eq!(
+            bloom.target_false_positive_rate(),
+            budget.target_false_positive_rate()
+        );
+        // The warning gives the size the requested rate needed, not the capped one
+        let warning = report.warning.unwrap();
+        assert!(
+            warning.contains("takes 17972 bytes, over max_bytes 4096"),
+            "{}",
+            warning
+        );
+    }
+
+    #[test]
+    fn max_bytes_honored() {
+        let (bloom, report) = BloomFilter::builder()
+            .expected_items(10_000)
+            .false_positive_rate(0.01)
+            .max_bytes(1 << 20)
+            .build_with_report()
+            .unwrap();
+        assert_eq!(bloom, BloomFilter::new(10_000, 0.01));
+        assert_eq!(report.false_positive_rate, 0.01);
+        assert_eq!(report.warning, None);
+
+        // A cap the filter fits in exactly doesn't bind either
+        let bloom = BloomFilter::builder()
+            .expected_items(10_000)
+            .max_bytes(bloom.size())
+            .build()
+            .unwrap();
+        assert_eq!(bloom, BloomFilter::new(10_000, 0.01));
+
+        assert!(matches!(
+            BloomFilter::builder()
+                .expected_items(10_000)
+                .max_bytes(0)
+                .build(),
+            Err(BloomError::InvalidParameter(_))
+        ));
+        // `m` would overflow, even though the cap isn't reached
+        assert!(matches!(
+            BloomFilter::builder()
+                .expected_items(1_000)
+                .max_bytes(usize::MAX)
+                .build(),
+            Err(BloomError::InvalidParameter(_))
+        ));
+        assert!(BloomFilter::builder()
+            .expected_items(1_000)
+            .max_bytes(usize::MAX / 8)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn missing_expected_items() {
+        assert!(matches!(
+            BloomFilter::builder().false_positive_rate(0.01).build(),
+            Err(BloomError::InvalidParameter(_))
+        ));
+    }
+}
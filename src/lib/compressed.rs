@@ -0,0 +1,301 @@
+//! A compressed binary format for [`BloomFilter`], enabled by the `compression` feature.
+//!
+//! The format is the one written by [`BloomFilter::to_bytes`] with its own magic number,
+//! `BLMZ`, and the bit array run-length encoded. The encoded bit array is a sequence of
+//! runs, each a count of zero bytes followed by a count of literal bytes and the literal
+//! bytes themselves, both counts as unsigned LEB128 varints. Runs of two or more zero bytes
+//! are collapsed, so a sparsely populated filter shrinks to a fraction of its size, while a
+//! dense one grows by at most a few bytes.
+//!
+//! [`BloomFilter`]: crate::BloomFilter
+
+use crate::{
+    BloomError, BloomFilter, FORMAT_VERSION, HEADER_LEN, HEADER_LEN_V2, HEADER_LEN_V3, MAGIC,
+};
+use alloc::vec::Vec;
+use bitvec::mem::BitRegister;
+use bitvec::store::BitStore;
+use core::hash::BuildHasher;
+
+const COMPRESSED_MAGIC: &[u8; 4] = b"BLMZ";
+
+// Shortest run of zero bytes worth ending a literal run for
+const MIN_ZERO_RUN: usize = 2;
+
+impl BloomFilter {
+    /// Deserialize a Bloom filter previously written by [`BloomFilter::to_bytes_compressed`].
+    ///
+    /// Returns the same errors as [`BloomFilter::from_bytes`], and [`BloomError::Corrupt`]
+    /// if the bit array decompresses to more than can be allocated.
+    pub fn from_bytes_compressed(bytes: &[u8]) -> Result<BloomFilter, BloomError> {
+        if bytes.len() < HEADER_LEN_V2 {
+            return Err(BloomError::UnexpectedEof);
+        }
+        if &bytes[..4] != COMPRESSED_MAGIC {
+            return Err(BloomError::Corrupt);
+        }
+        // Compressed filters were introduced in version 2, so there are no version 1 ones
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        let header_len = match version {
+            2 => HEADER_LEN_V2,
+            3 => HEADER_LEN_V3,
+            FORMAT_VERSION => HEADER_LEN,
+            _ => return Err(BloomError::UnsupportedVersion(version)),
+        };
+        if bytes.len() < header_len {
+            return Err(BloomError::UnexpectedEof);
+        }
+        let (header, mut encoded) = bytes.split_at(header_len);
+        let m = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let num_bytes = usize::try_from(m.div_ceil(8)).map_err(|_| BloomError::Corrupt)?;
+
+        // Rebuild the uncompressed form and let `from_bytes` validate it. The buffer grows run
+        // by run, as `m` is untrusted and a small input may claim a huge filter
+        let mut raw = Vec::with_capacity(header_len + encoded.len());
+        raw.extend_from_slice(MAGIC);
+        raw.extend_from_slice(&header[4..]);
+        while !encoded.is_empty() {
+            let zeros = read_varint(&mut encoded)?;
+            let literals = read_varint(&mut encoded)?;
+            if zeros.saturating_add(literals) > header_len + num_bytes - raw.len() {
+                return Err(BloomError::Corrupt);
+            }
+            if encoded.len() < literals {
+                return Err(BloomError::UnexpectedEof);
+            }
+            let (literal, rest) = encoded.split_at(literals);
+            raw.try_reserve(zeros + literals)
+                .map_err(|_| BloomError::Corrupt)?;
+            raw.resize(raw.len() + zeros, 0);
+            raw.extend_from_slice(literal);
+            encoded = rest;
+        }
+        BloomFilter::from_bytes(&raw)
+    }
+}
+
+impl<S: BuildHasher, W: BitRegister + BitStore> BloomFilter<S, W>
+where
+    S::Hasher: 'static,
+{
+    /// Serialize the Bloom filter like [`BloomFilter::to_bytes`], but with the bit array
+    /// run-length encoded so that sparsely populated filters take up less space. The size of
+    /// the compressed filter is the length of the returned bytes.
+    ///
+    /// It can be loaded back with [`BloomFilter::from_bytes_compressed`].
+    pub fn to_bytes_compressed(&self) -> Vec<u8> {
+        let bytes = self.to_bytes();
+        let (header, mut raw) = bytes.split_at(HEADER_LEN);
+
+        let mut compressed = Vec::with_capacity(HEADER_LEN);
+        compressed.extend_from_slice(COMPRESSED_MAGIC);
+        compressed.extend_from_slice(&header[4..]);
+        while !raw.is_empty() {
+            let zeros = raw.iter().take_while(|&&b| b == 0).count();
+            raw = &raw[zeros..];
+            let literals = literal_run_len(raw);
+            write_varint(&mut compressed, zeros);
+            write_varint(&mut compressed, literals);
+            compressed.extend_from_slice(&raw[..literals]);
+            raw = &raw[literals..];
+        }
+        compressed
+    }
+}
+
+/// Returns the length of the literal run at the start of `raw`, which ends at the first run
+/// of at least [`MIN_ZERO_RUN`] zero bytes.
+fn literal_run_len(raw: &[u8]) -> usize {
+    let mut len = 0;
+    while len < raw.len() {
+        let zeros = raw[len..].iter().take_while(|&&b| b == 0).count();
+        if zeros >= MIN_ZERO_RUN || len + zeros == raw.len() {
+            return len;
+        }
+        len += zeros.max(1);
+    }
+    len
+}
+
+/// Append `value` to `bytes` as an unsigned LEB128 varint.
+fn write_varint(bytes: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Read an unsigned LEB128 varint from the start of `bytes`, advancing past it.
+fn read_varint(bytes: &mut &[u8]) -> Result<usize, BloomError> {
+    let mut value: usize = 0;
+    for shift in (0..usize::BITS).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or(BloomError::UnexpectedEof)?;
+        *bytes = rest;
+        let low = (byte & 0x7f) as usize;
+        // Reject varints whose bits don't fit in a `usize`
+        if (low << shift) >> shift != low {
+            return Err(BloomError::Corrupt);
+        }
+        value |= low << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(BloomError::Corrupt)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compressed::*;
+
+    #[test]
+    fn sparse_round_trip() {
+        let mut bloom = BloomFilter::new_with_seed(100_000, 0.01, 7);
+        bloom.extend(0..100);
+
+        let compressed = bloom.to_bytes_compressed();
+        assert!(
+            compressed.len() * 10 < bloom.to_bytes().len(),
+            "compressed={} raw={}",
+            compressed.len(),
+            bloom.to_bytes().len()
+        );
+        assert_eq!(
+            BloomFilter::from_bytes_compressed(&compressed).unwrap(),
+            bloom
+        );
+    }
+
+    #[test]
+    fn dense_round_trip() {
+        for n in [0, 1, 1_000, 10_000] {
+            let mut bloom = BloomFilter::new(1_000, 0.01);
+            bloom.extend(0..n);
+            let compressed = bloom.to_bytes_compressed();
+            assert!(compressed.len() <= bloom.to_bytes().len() + 8);
+            assert_eq!(
+                BloomFilter::from_bytes_compressed(&compressed).unwrap(),
+                bloom
+            );
+        }
+    }
+
+    #[test]
+    fn version_3() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        bloom.insert(&"hi");
+
+        // Version 3 headers have no hash scheme
+        let mut compressed = bloom.to_bytes_compressed();
+        compressed[4..8].copy_from_slice(&3_u32.to_le_bytes());
+        compressed.remove(HEADER_LEN_V3);
+        assert_eq!(
+            BloomFilter::from_bytes_compressed(&compressed).unwrap(),
+            bloom
+        );
+    }
+
+    #[test]
+    fn version_2() {
+        // Written by the first build with compressed filters: `new_with_seed(100, 0.01, 42)`
+        // holding "hi", "yo" and "sup"
+        let bytes = include_bytes!("testdata/compressed_v2.bin");
+        let bloom = BloomFilter::from_bytes_compressed(bytes).unwrap();
+        assert_eq!(bloom.num_bits(), 960);
+        assert_eq!(bloom.num_hash_functions(), 6);
+        assert_eq!(bloom.seed(), 42);
+        for item in ["hi", "yo", "sup"] {
+            assert!(bloom.contains(&item));
+        }
+
+        let mut expected = BloomFilter::new_with_seed(100, 0.01, 42);
+        expected.insert_all(&["hi", "yo", "sup"]);
+        assert_eq!(bloom.as_raw_slice(), expected.as_raw_slice());
+    }
+
+    #[test]
+    fn huge_num_bits() {
+        let bloom = BloomFilter::new(1_000, 0.01);
+        let mut header = bloom.to_bytes_compressed()[..HEADER_LEN].to_vec();
+
+        // A short input claiming a huge filter isn't allocated up front
+        header[8..16].copy_from_slice(&(1_u64 << 50).to_le_bytes());
+        let mut bytes = header.clone();
+        bytes.extend_from_slice(&[0, 3, 1, 2, 3, 0, 2, 4, 5, 0, 0]);
+        assert_eq!(
+            BloomFilter::from_bytes_compressed(&bytes),
+            Err(BloomError::UnexpectedEof)
+        );
+
+        // Nor is a zero run too large to allocate
+        header[8..16].copy_from_slice(&(1_u64 << 62).to_le_bytes());
+        let mut bytes = header;
+        write_varint(&mut bytes, 1 << 58);
+        write_varint(&mut bytes, 0);
+        assert_eq!(
+            BloomFilter::from_bytes_compressed(&bytes),
+            Err(BloomError::Corrupt)
+        );
+    }
+
+    #[test]
+    fn literal_runs() {
+        assert_eq!(literal_run_len(&[]), 0);
+        assert_eq!(literal_run_len(&[1, 2, 0, 3, 0, 0, 4]), 4);
+        assert_eq!(literal_run_len(&[1, 0]), 1);
+        assert_eq!(literal_run_len(&[1, 0, 2]), 3);
+    }
+
+    #[test]
+    fn varint() {
+        for value in [0, 1, 0x7f, 0x80, 300, usize::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value);
+            let mut slice = &bytes[..];
+            assert_eq!(read_varint(&mut slice).unwrap(), value);
+            assert!(slice.is_empty());
+        }
+        assert_eq!(
+            read_varint(&mut &[0x80][..]),
+            Err(BloomError::UnexpectedEof)
+        );
+        assert_eq!(read_varint(&mut &[0xff; 11][..]), Err(BloomError::Corrupt));
+    }
+
+    #[test]
+    fn errors() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        bloom.insert(&"hi");
+        let compressed = bloom.to_bytes_compressed();
+
+        assert_eq!(
+            BloomFilter::from_bytes_compressed(&bloom.to_bytes()),
+            Err(BloomError::Corrupt)
+        );
+        assert_eq!(
+            BloomFilter::from_bytes_compressed(&compressed[..compressed.len() - 1]),
+            Err(BloomError::UnexpectedEof)
+        );
+        for version in [1_u32, 99] {
+            let mut bytes = compressed.clone();
+            bytes[4..8].copy_from_slice(&version.to_le_bytes());
+            assert_eq!(
+                BloomFilter::from_bytes_compressed(&bytes),
+                Err(BloomError::UnsupportedVersion(version))
+            );
+        }
+        let mut overlong = compressed.clone();
+        overlong.extend_from_slice(&[1, 0]);
+        assert_eq!(
+            BloomFilter::from_bytes_compressed(&overlong),
+            Err(BloomError::Corrupt)
+        );
+        let mut zero_bits = compressed[..HEADER_LEN].to_vec();
+        zero_bits[8..16].copy_from_slice(&0_u64.to_le_bytes());
+        assert_eq!(
+            BloomFilter::from_bytes_compressed(&zero_bits),
+            Err(BloomError::Corrupt)
+        );
+    }
+}
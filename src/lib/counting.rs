@@ -0,0 +1,174 @@
+//! A counting Bloom filter, which supports removing items.
+
+use crate::{calc_index, calc_k, calc_m, hash_pair, DefaultHashBuilder};
+use core::hash::{BuildHasher, Hash};
+
+/// Counting Bloom filter data structure.
+///
+/// Rather than a single bit, each slot holds an 8-bit counter. Inserting an item increments
+/// its *`k`* counters and removing it decrements them, so that, unlike a
+/// [`BloomFilter`], items can be removed.
+///
+/// Counters saturate at `255`. A saturated counter's true count is unknown, so it is never
+/// decremented again: decrementing it could make other items sharing the counter look
+/// absent, i.e. cause false negatives. The cost is that items mapping to a saturated counter
+/// can't be fully removed and may keep being reported as contained.
+///
+/// [`BloomFilter`]: crate::BloomFilter
+#[derive(Debug, Clone)]
+pub struct CountingBloomFilter<S = DefaultHashBuilder> {
+    // Number of hash functions
+    num_hash_functions: usize,
+
+    // Counters, one per slot
+    counters: Vec<u8>,
+
+    // Builds the hashers applied to items
+    hash_builder: S,
+}
+
+impl CountingBloomFilter {
+    /// Instantiate a new [`CountingBloomFilter`] by providing the expected `num_elements`
+    /// that will be added to the filter and the target `false_positive_rate`.
+    ///
+    /// [`CountingBloomFilter`]: CountingBloomFilter
+    pub fn new(num_elements: usize, false_positive_rate: f32) -> CountingBloomFilter {
+        CountingBloomFilter::with_hasher(
+            num_elements,
+            false_positive_rate,
+            DefaultHashBuilder::default(),
+        )
+    }
+}
+
+impl<S: BuildHasher> CountingBloomFilter<S>
+where
+    S::Hasher: 'static,
+{
+    /// Instantiate a new [`CountingBloomFilter`] like [`CountingBloomFilter::new`], hashing
+    /// items with hashers built by `hash_builder`.
+    ///
+    /// [`CountingBloomFilter`]: CountingBloomFilter
+    pub fn with_hasher(
+        num_elements: usize,
+        false_positive_rate: f32,
+        hash_builder: S,
+    ) -> CountingBloomFilter<S> {
+        let m = calc_m(num_elements, false_positive_rate);
+        let k = calc_k(num_elements, m);
+
+        CountingBloomFilter {
+            num_hash_functions: k,
+            counters: vec![0; m],
+            hash_builder,
+        }
+    }
+
+    /// Returns the number of hash functions, `k`.
+    pub fn num_hash_functions(&self) -> usize {
+        self.num_hash_functions
+    }
+
+    /// Returns the number of counters, `m`.
+    pub fn num_counters(&self) -> usize {
+        self.counters.len()
+    }
+
+    /// Insert an item into the filter, incrementing each of its *`k`* counters.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        let (h1, h2) = hash_pair(&self.hash_builder, 0, item);
+        for i in 0..self.num_hash_functions {
+            let c = calc_index(h1, h2, i, self.counters.len());
+            self.counters[c] = self.counters[c].saturating_add(1);
+        }
+    }
+
+    /// Remove an item from the filter, decrementing each of its *`k`* counters, and return
+    /// whether it was contained.
+    ///
+    /// Nothing is removed if the item isn't contained. Only remove items that were actually
+    /// inserted: removing a false positive decrements counters that belong to other items and
+    /// can cause false negatives.
+    pub fn remove<T: Hash + ?Sized>(&mut self, item: &T) -> bool {
+        if !self.contains(item) {
+            return false;
+        }
+
+        let (h1, h2) = hash_pair(&self.hash_builder, 0, item);
+        for i in 0..self.num_hash_functions {
+            let c = calc_index(h1, h2, i, self.counters.len());
+            if self.counters[c] != u8::MAX {
+                self.counters[c] -= 1;
+            }
+        }
+        true
+    }
+
+    /// Returns whether the filter contains the item, i.e. all of its *`k`* counters are
+    /// nonzero. It may return a false positive but will never return a false negative,
+    /// provided only inserted items are removed.
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        let (h1, h2) = hash_pair(&self.hash_builder, 0, item);
+        (0..self.num_hash_functions)
+            .all(|i| self.counters[calc_index(h1, h2, i, self.counters.len())] != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::counting::*;
+
+    #[test]
+    fn insert_remove() {
+        let mut bloom = CountingBloomFilter::new(100_000, 0.01);
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+        assert!(bloom.remove(&"hi"));
+        assert!(!bloom.contains(&"hi"));
+    }
+
+    #[test]
+    fn remove_absent() {
+        let mut bloom = CountingBloomFilter::new(100_000, 0.01);
+        bloom.insert(&"hi");
+        assert!(!bloom.remove(&"yo"));
+        assert!(bloom.contains(&"hi"));
+    }
+
+    #[test]
+    fn insert_twice_remove_once() {
+        let mut bloom = CountingBloomFilter::new(100_000, 0.01);
+        bloom.insert(&"hi");
+        bloom.insert(&"hi");
+        assert!(bloom.remove(&"hi"));
+        assert!(bloom.contains(&"hi"));
+        assert!(bloom.remove(&"hi"));
+        assert!(!bloom.contains(&"hi"));
+    }
+
+    #[test]
+    fn remove_keeps_others() {
+        let mut bloom = CountingBloomFilter::new(1_000, 0.01);
+        for i in 0..1_000 {
+            bloom.insert(&i);
+        }
+        for i in 0..500 {
+            assert!(bloom.remove(&i));
+        }
+        for i in 500..1_000 {
+            assert!(bloom.contains(&i));
+        }
+    }
+
+    #[test]
+    fn saturated_counters_stick() {
+        let mut bloom = CountingBloomFilter::new(100, 0.01);
+        for _ in 0..300 {
+            bloom.insert(&"hi");
+        }
+        for _ in 0..300 {
+            bloom.remove(&"hi");
+        }
+        assert!(bloom.contains(&"hi"));
+    }
+}
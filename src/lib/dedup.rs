@@ -0,0 +1,129 @@
+//! A streaming approximate deduplication stage built on a [`BloomFilter`].
+//!
+//! [`BloomFilter`]: crate::BloomFilter
+
+use crate::{BloomFilter, DefaultHashBuilder};
+use bitvec::mem::BitRegister;
+use bitvec::store::BitStore;
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+
+impl<S: BuildHasher, W: BitRegister + BitStore> BloomFilter<S, W>
+where
+    S::Hasher: 'static,
+{
+    /// Returns an iterator over the items of `iter` the Bloom filter hasn't seen yet,
+    /// inserting each one as it passes, so that later duplicates of it are dropped.
+    ///
+    /// ```
+    /// use bloom::BloomFilter;
+    ///
+    /// let bloom = BloomFilter::new(100, 0.01);
+    /// let unique: Vec<_> = bloom.dedup_iter(["a", "b", "a", "c", "b"]).collect();
+    /// assert_eq!(unique, ["a", "b", "c"]);
+    /// ```
+    ///
+    /// Deduplication is approximate: an item is dropped when all of its bits are already
+    /// set, so a false positive drops an item that was in fact unique. Duplicates are never
+    /// let through, so the output is a subset of the input without repeats, but size the
+    /// filter for the number of distinct items expected to keep such losses rare. Items seen
+    /// before the stream, e.g. inserted into the filter earlier, are dropped too.
+    pub fn dedup_iter<I>(self, iter: I) -> DedupIter<I::IntoIter, S, W>
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        DedupIter {
+            iter: iter.into_iter(),
+            bloom: self,
+        }
+    }
+}
+
+/// An iterator yielding the items of another iterator not yet seen by a [`BloomFilter`],
+/// returned by [`BloomFilter::dedup_iter`].
+///
+/// [`BloomFilter`]: crate::BloomFilter
+/// [`BloomFilter::dedup_iter`]: crate::BloomFilter::dedup_iter
+#[derive(Debug, Clone)]
+pub struct DedupIter<I, S = DefaultHashBuilder, W: BitRegister + BitStore = u8> {
+    iter: I,
+    bloom: BloomFilter<S, W>,
+}
+
+impl<I, S, W: BitRegister + BitStore> DedupIter<I, S, W> {
+    /// Returns the Bloom filter, holding every item seen so far.
+    pub fn as_filter(&self) -> &BloomFilter<S, W> {
+        &self.bloom
+    }
+
+    /// Consumes the iterator, returning the Bloom filter holding every item seen so far, e.g.
+    /// to deduplicate another stream against the same items.
+    pub fn into_filter(self) -> BloomFilter<S, W> {
+        self.bloom
+    }
+}
+
+impl<I, S, W> Iterator for DedupIter<I, S, W>
+where
+    I: Iterator,
+    I::Item: Hash,
+    S: BuildHasher,
+    S::Hasher: 'static,
+    W: BitRegister + BitStore,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let bloom = &mut self.bloom;
+        self.iter.find(|item| bloom.insert_check(item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Any number of the remaining items may be duplicates
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<I, S, W> FusedIterator for DedupIter<I, S, W>
+where
+    I: FusedIterator,
+    I::Item: Hash,
+    S: BuildHasher,
+    S::Hasher: 'static,
+    W: BitRegister + BitStore,
+{
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::dedup::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn drops_duplicates() {
+        let input: Vec<u64> = (0..5_000).chain(2_500..7_500).map(|i| i % 6_000).collect();
+        let bloom = BloomFilter::new(10_000, 0.001);
+        let output: Vec<u64> = bloom.dedup_iter(&input).copied().collect();
+
+        let unique: HashSet<u64> = output.iter().copied().collect();
+        assert_eq!(unique.len(), output.len());
+        let input: HashSet<u64> = input.into_iter().collect();
+        assert!(unique.is_subset(&input));
+        // Only false positives are lost
+        assert!(output.len() > input.len() * 99 / 100);
+    }
+
+    #[test]
+    fn into_filter() {
+        let mut bloom = BloomFilter::new(100, 0.01);
+        bloom.insert(&1);
+        let mut iter = bloom.dedup_iter([1, 2, 2, 3]);
+        assert_eq!(iter.next(), Some(2));
+        assert!(iter.as_filter().contains(&2));
+        assert!(!iter.as_filter().contains(&3));
+
+        let bloom = iter.into_filter();
+        assert_eq!(bloom.dedup_iter([1, 2, 3, 4]).collect::<Vec<_>>(), [3, 4]);
+    }
+}
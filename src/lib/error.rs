@@ -0,0 +1,45 @@
+use alloc::string::String;
+use core::fmt;
+
+/// Errors returned by fallible [`BloomFilter`] operations.
+///
+/// [`BloomFilter`]: crate::BloomFilter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BloomError {
+    /// The input ended before a complete filter could be read.
+    UnexpectedEof,
+
+    /// The input is not a valid serialized filter.
+    Corrupt,
+
+    /// The input was written with a format version this build cannot read.
+    UnsupportedVersion(u32),
+
+    /// The input was written by a build deriving items' bits from their hashes with another
+    /// scheme, whose version is given, so it can't be queried correctly.
+    UnsupportedHashScheme(u8),
+
+    /// The filters don't share the same size in bits and number of hash functions.
+    MismatchedParameters,
+
+    /// A parameter used to construct a filter is invalid.
+    InvalidParameter(String),
+}
+
+impl fmt::Display for BloomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BloomError::UnexpectedEof => write!(f, "unexpected end of input"),
+            BloomError::Corrupt => write!(f, "corrupt Bloom filter data"),
+            BloomError::UnsupportedVersion(v) => write!(f, "unsupported format version {}", v),
+            BloomError::UnsupportedHashScheme(v) => {
+                write!(f, "unsupported hash scheme version {}", v)
+            }
+            BloomError::MismatchedParameters => write!(f, "mismatched Bloom filter parameters"),
+            BloomError::InvalidParameter(msg) => write!(f, "invalid parameter: {}", msg),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BloomError {}
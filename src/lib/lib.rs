@@ -4,7 +4,8 @@
 //! probabilistic data structure that is used to test whether an element is a member of a
 //! set. False positive matches are possible, but false negatives are not. Thus, they are
 //! useful for situations where the query answer is expected to be "not a member" most of
-//! the time. Elements can be added to the set, but not removed.
+//! the time. Elements can be added to the set, but not removed; see
+//! [`CountingBloomFilter`] for a variant that supports removal.
 //!
 //! Example:
 //!
@@ -18,19 +19,295 @@
 //! ```
 //!
 //! [Bloom filter]: https://en.wikipedia.org/wiki/Bloom_filter
+//!
+//! Enable the `serde` feature to serialize and deserialize filters with [`serde`].
+//!
+//! [`serde`]: https://serde.rs
+//!
+//! Enable the `compression` feature for [`BloomFilter::to_bytes_compressed`], a run-length
+//! encoded serialization that shrinks sparsely populated filters.
+//!
+//! Enable the `roaring` feature for [`BloomFilter::to_roaring_bytes`], which exports the set
+//! bits as a portable Roaring bitmap for analytics tooling.
+//!
+//! Enable the `metrics` feature for [`BloomFilter::metrics`], which counts a filter's inserts,
+//! lookups and hashes computed, and times its inserts and lookups.
+//!
+//! Enable the `wasm` feature for [`WasmBloomFilter`], a wrapper whose API only exchanges
+//! numbers, booleans and byte arrays, for use from JavaScript.
+//!
+//! The crate is `no_std` compatible, requiring only `alloc`, when the default `std` feature
+//! is disabled. Sizing filters from an expected number of elements and false positive rate,
+//! and estimating their count and false positive rate, need floating point math from `std`,
+//! so without it filters are built with [`BloomFilter::with_params`] or
+//! [`BloomFilter::with_params_and_hasher`], and the builder, [`CountingBloomFilter`],
+//! [`ScalableBloomFilter`], [`TimedBloomFilter`], [`BlockedBloomFilter`],
+//! [`PartitionedBloomFilter`], [`AtomicBloomFilter`] and [`FromIterator`] are unavailable. The default SipHash hasher
+//! doesn't need `std`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(all(feature = "std", target_has_atomic = "64"))]
+pub mod atomic;
+#[cfg(feature = "std")]
+pub mod blocked;
+#[cfg(feature = "std")]
+mod builder;
+#[cfg(feature = "compression")]
+mod compressed;
+#[cfg(feature = "std")]
+pub mod counting;
+mod dedup;
+mod error;
+mod metrics;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "std")]
+pub mod partitioned;
+#[cfg(feature = "roaring")]
+mod roaring_impl;
+#[cfg(feature = "std")]
+pub mod scalable;
+#[cfg(feature = "serde")]
+mod serde_impl;
+#[cfg(feature = "std")]
+pub mod timed;
+pub mod typed;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(all(feature = "std", target_has_atomic = "64"))]
+pub use atomic::AtomicBloomFilter;
+#[cfg(feature = "std")]
+pub use blocked::BlockedBloomFilter;
+#[cfg(feature = "std")]
+pub use builder::{BloomFilterBuilder, BuildReport};
+#[cfg(feature = "std")]
+pub use counting::CountingBloomFilter;
+pub use dedup::DedupIter;
+pub use error::BloomError;
+#[cfg(feature = "metrics")]
+pub use metrics::Metrics;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapBloomFilter;
+#[cfg(feature = "std")]
+pub use partitioned::PartitionedBloomFilter;
+#[cfg(feature = "std")]
+pub use scalable::ScalableBloomFilter;
+#[cfg(feature = "std")]
+pub use timed::TimedBloomFilter;
+pub use typed::TypedBloomFilter;
+#[cfg(feature = "wasm")]
+pub use wasm::WasmBloomFilter;
+
+use alloc::vec::Vec;
+use bitvec::mem::BitRegister;
 use bitvec::prelude::*;
+use core::any::{Any, TypeId};
+use core::f64::consts::LN_2;
+use core::fmt;
+use core::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use metrics::Recorder;
 use siphasher::sip::SipHasher;
-use std::hash::{Hash, Hasher};
+use siphasher::sip128::{Hasher128, SipHasher as Sip128Hasher};
+
+/// The magic number every filter serialized by [`BloomFilter::to_bytes`] starts with, so
+/// that tools can recognize filter files without deserializing them.
+pub const MAGIC: &[u8; 4] = b"BLMF";
+
+/// The version of the format written by [`BloomFilter::to_bytes`], stored little-endian
+/// right after [`MAGIC`]. [`BloomFilter::from_bytes`] reads this version and all earlier
+/// ones.
+pub const FORMAT_VERSION: u32 = 4;
+
+/// The most hash functions a [`BloomFilter`] can have. Filters are never sized with more,
+/// as each one costs every insert and lookup a bit access, and a false positive rate needing
+/// more is below what an `f64` can represent. [`BloomFilter::from_bytes`] rejects filters
+/// claiming more, so that untrusted input can't make lookups loop for ages.
+pub const MAX_NUM_HASH_FUNCTIONS: usize = 256;
+
+// Serialized header: magic, format version (u32), `m` (u64), `k` (u64), since version 2 the
+// seed (u64), since version 3 the expected number of elements (u64) and target false
+// positive rate (f64), and since version 4 the hash scheme (u8).
+const HEADER_LEN: usize = MAGIC.len() + 4 + 8 + 8 + 8 + 8 + 8 + 1;
+const HEADER_LEN_V3: usize = HEADER_LEN - 1;
+const HEADER_LEN_V2: usize = HEADER_LEN_V3 - 16;
+const HEADER_LEN_V1: usize = HEADER_LEN_V2 - 8;
+
+// Version of the scheme deriving an item's bit indices from it, `hash_pair` and
+// `calc_index`, recorded in serialized filters. It must be bumped whenever either changes,
+// so that filters written under the old scheme are rejected rather than silently queried
+// with the wrong bits.
+const HASH_SCHEME_VERSION: u8 = 1;
+
+// Flag set in the recorded hash scheme of filters deriving their bit indices by enhanced
+// double hashing, `calc_index_enhanced`, rather than `calc_index`.
+const ENHANCED_DOUBLE_HASHING: u8 = 0x80;
+
+// Smallest size in bits of a filter built from `n` and `f`, so that `size()` is nonzero
+#[cfg(feature = "std")]
+const MIN_NUM_BITS: usize = 8;
+
+// Sizing of a default filter.
+#[cfg(feature = "std")]
+const DEFAULT_NUM_ELEMENTS: usize = 1_000;
+#[cfg(feature = "std")]
+const DEFAULT_FALSE_POSITIVE_RATE: f32 = 0.01;
+
+// Sizing used when collecting a filter from an iterator.
+#[cfg(feature = "std")]
+const FROM_ITER_MIN_ELEMENTS: usize = 1_000;
+#[cfg(feature = "std")]
+const FROM_ITER_FALSE_POSITIVE_RATE: f32 = 0.01;
+
+/// The default [`BuildHasher`] of a [`BloomFilter`]: SipHash with fixed keys.
+///
+/// Unlike [`RandomState`], every filter hashes items identically, so filters built in
+/// different processes can be serialized, merged and compared.
+///
+/// [`BloomFilter`]: BloomFilter
+/// [`RandomState`]: std::collections::hash_map::RandomState
+pub type DefaultHashBuilder = BuildHasherDefault<SipHasher>;
+
+/// A [`BuildHasher`] for 128-bit SipHash with fixed keys.
+///
+/// Filters built with it, e.g. by [`BloomFilter::with_hasher`], derive both of an item's
+/// hashes from the two halves of a single 128-bit hash, so each item is hashed once by one
+/// hasher rather than twice by two. This makes inserts and lookups of large items faster.
+/// The bits set differ from those of a [`DefaultHashBuilder`] filter, so the two can't be
+/// merged or compared.
+pub type Sip128HashBuilder = BuildHasherDefault<Sip128Hasher>;
+
+/// A [`BuildHasher`] for [`IdentityHasher`], whose hashes are trivially predictable.
+///
+/// It's meant for tests that assert exactly which bits a filter sets; see [`IdentityHasher`]
+/// for the bits an item maps to. Since an adversary can choose items that collide at will
+/// and small integers only touch the start of the filter, don't use it otherwise.
+pub type IdentityHashBuilder = BuildHasherDefault<IdentityHasher>;
+
+/// A [`Hasher`] whose hash is the wrapping sum of the integers written to it, each byte of a
+/// byte string counting as one integer. Signed integers are summed as their two's complement
+/// bits.
+///
+/// A filter with its [`IdentityHashBuilder`] and no seed hashes an integer item `x` to
+/// *`h1 = x`* and, because the second hasher is fed a prefix `1`, *`h2 = x + 1`*. Its
+/// *`i`*-th bit is then *`(h1 + i*(h2 | 1)) mod m`*, so with *`m = 64`* and *`k = 2`* the item
+/// `3u64` sets bits `3` and `8`:
+///
+/// ```
+/// use bloom::{BloomFilter, IdentityHashBuilder};
+///
+/// let mut bloom = BloomFilter::with_params_and_hasher(64, 2, IdentityHashBuilder::default())
+///     .unwrap();
+/// bloom.insert(&3_u64);
+/// assert_eq!(bloom.set_bits().collect::<Vec<_>>(), [3, 8]);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IdentityHasher(u64);
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_u8(*byte);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0 = self.0.wrapping_add(i);
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write_u64(i as u64);
+        self.write_u64((i >> 64) as u64);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i8(&mut self, i: i8) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as u64);
+    }
+}
 
 /// Bloom filter data structure.
-#[derive(Debug)]
-pub struct BloomFilter {
+///
+/// Items are hashed with hashers built by `S`, which defaults to [`DefaultHashBuilder`].
+/// Faster non-cryptographic hashers can be plugged in with [`BloomFilter::with_hasher`].
+///
+/// The bits are stored in words of type `W`, bytes by default. Wider words, selected with
+/// [`BloomFilterBuilder::storage`], speed up operations that scan the whole bit vector, like
+/// [`BloomFilter::estimate_count`], [`BloomFilter::union`] and [`BloomFilter::clear`]. The
+/// word size doesn't affect which bits are set or the serialized form.
+///
+/// Two filters compare equal when they have the same number of hash functions, the same
+/// seed, derive bit indices the same way and have identical bit vectors.
+#[derive(Clone)]
+pub struct BloomFilter<S = DefaultHashBuilder, W: BitRegister + BitStore = u8> {
     // Number of hash functions
     num_hash_functions: usize,
 
     // Bit vector storing Bloom filter
-    bits: BitVec<u8>,
+    bits: BitVec<W>,
+
+    // Mixed into the hashes of every item; 0 leaves them unchanged
+    seed: u64,
+
+    // Number of items and false positive rate the filter was designed for
+    expected_elements: usize,
+    target_false_positive_rate: f64,
+
+    // Number of inserts that set at least one new bit
+    inserted: u64,
+
+    // Whether bit indices are derived by enhanced rather than plain double hashing
+    enhanced_double_hashing: bool,
+
+    // Builds the hashers applied to items
+    hash_builder: S,
+
+    // Counts operations when the `metrics` feature is enabled
+    metrics: Recorder,
 }
 
 impl BloomFilter {
@@ -38,114 +315,3438 @@ impl BloomFilter {
     /// be added to the Bloom filter and the target `false_positive_rate`.
     ///
     /// [`BloomFilter`]: BloomFilter
+    #[cfg(feature = "std")]
     pub fn new(num_elements: usize, false_positive_rate: f32) -> BloomFilter {
-        let m = calc_m(num_elements, false_positive_rate);
-        let k = calc_k(num_elements, m);
+        BloomFilter::with_hasher(
+            num_elements,
+            false_positive_rate,
+            DefaultHashBuilder::default(),
+        )
+    }
+
+    /// Instantiate a new [`BloomFilter`] like [`BloomFilter::new`], with a target false
+    /// positive rate of one in `one_in`, e.g. `new_one_in(n, 100)` for a rate of `0.01`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `one_in` is less than 2.
+    ///
+    /// [`BloomFilter`]: BloomFilter
+    #[cfg(feature = "std")]
+    pub fn new_one_in(num_elements: usize, one_in: u32) -> BloomFilter {
+        assert!(one_in >= 2, "one_in must be at least 2, got {}", one_in);
+        BloomFilter::with_sizing(
+            num_elements,
+            1.0 / one_in as f64,
+            DefaultHashBuilder::default(),
+        )
+    }
+
+    /// Instantiate a new [`BloomFilter`] like [`BloomFilter::new`], mixing `seed` into the
+    /// hashes of every item.
+    ///
+    /// Filters with different seeds set different bits for the same items, so a random seed
+    /// makes it impractical for an adversary to pick items that collide, while a fixed seed
+    /// keeps the bits reproducible. A seed of `0` hashes items like [`BloomFilter::new`].
+    ///
+    /// [`BloomFilter`]: BloomFilter
+    #[cfg(feature = "std")]
+    pub fn new_with_seed(num_elements: usize, false_positive_rate: f32, seed: u64) -> BloomFilter {
+        let mut bloom = BloomFilter::new(num_elements, false_positive_rate);
+        bloom.seed = seed;
+        bloom
+    }
+
+    /// Instantiate a new [`BloomFilter`] sized for `count` items at `false_positive_rate`, like
+    /// [`BloomFilter::new`], and insert all of `items` into it.
+    ///
+    /// Useful for rebuilding a filter at a different rate from the data it was built from,
+    /// sizing it for the number of items rather than for the old filter's capacity. `count`
+    /// should be the number of distinct items, or the filter ends up over or under sized.
+    ///
+    /// [`BloomFilter`]: BloomFilter
+    #[cfg(feature = "std")]
+    pub fn rebuild_from<T: Hash, I: IntoIterator<Item = T>>(
+        items: I,
+        count: usize,
+        false_positive_rate: f64,
+    ) -> BloomFilter {
+        let mut bloom =
+            BloomFilter::with_sizing(count, false_positive_rate, DefaultHashBuilder::default());
+        bloom.extend(items);
+        bloom
+    }
+
+    /// Instantiate a new [`BloomFilter`] for `num_elements` items taking at most `max_bytes`
+    /// bytes, rather than one meeting a target false positive rate.
+    ///
+    /// The filter has all *`m = 8 * max_bytes`* bits available and the optimal number of
+    /// hash functions for `m` and `num_elements`. The false positive rate it achieves at
+    /// `num_elements` items, *`(1 - e^(-kn/m))^k`*, is its
+    /// [`target_false_positive_rate`](BloomFilter::target_false_positive_rate).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_bytes` is zero, or over `usize::MAX / 8` so that `m` overflows.
+    ///
+    /// [`BloomFilter`]: BloomFilter
+    #[cfg(feature = "std")]
+    pub fn with_memory_budget(num_elements: usize, max_bytes: usize) -> BloomFilter {
+        assert!(max_bytes > 0, "max_bytes must be greater than zero");
+        let (m, k, f) = calc_budget_sizing(num_elements, max_bytes)
+            .expect("max_bytes must be at most usize::MAX / 8");
+        BloomFilter::with_geometry(m, k, num_elements, f, DefaultHashBuilder::default())
+    }
+
+    /// Returns a [`BloomFilterBuilder`] for configuring a new [`BloomFilter`] step by step.
+    ///
+    /// [`BloomFilter`]: BloomFilter
+    #[cfg(feature = "std")]
+    pub fn builder() -> BloomFilterBuilder {
+        BloomFilterBuilder::new()
+    }
+
+    /// Instantiate a new [`BloomFilter`] directly from its size in bits, `num_bits`, and its
+    /// number of hash functions, `num_hash_functions`, rather than deriving them from the
+    /// expected number of elements and false positive rate.
+    ///
+    /// Returns [`BloomError::InvalidParameter`] if `num_bits` or `num_hash_functions` is zero,
+    /// or `num_hash_functions` is over [`MAX_NUM_HASH_FUNCTIONS`].
+    ///
+    /// [`BloomFilter`]: BloomFilter
+    pub fn with_params(
+        num_bits: usize,
+        num_hash_functions: usize,
+    ) -> Result<BloomFilter, BloomError> {
+        BloomFilter::with_params_and_hasher(
+            num_bits,
+            num_hash_functions,
+            DefaultHashBuilder::default(),
+        )
+    }
+
+    /// Instantiate a new [`BloomFilter`] from a geometry known ahead of time, its size in bits
+    /// `num_bits` and number of hash functions `k`, without any floating point math.
+    ///
+    /// Like [`BloomFilter::with_params`], but for sizes that are known to be valid, such as
+    /// constants computed once with [`optimal_params`] or in a build script:
+    ///
+    /// ```
+    /// use bloom::BloomFilter;
+    ///
+    /// // bloom::optimal_params(1_000, 0.01), rounded up to a whole number of bytes
+    /// const NUM_BITS: usize = 9_592;
+    /// const NUM_HASH_FUNCTIONS: usize = 6;
+    ///
+    /// let mut bloom = BloomFilter::from_bit_count(NUM_BITS, NUM_HASH_FUNCTIONS);
+    /// bloom.insert(&"hi");
+    /// assert!(bloom.contains(&"hi"));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_bits` or `k` is zero, or `k` is over [`MAX_NUM_HASH_FUNCTIONS`].
+    ///
+    /// [`BloomFilter`]: BloomFilter
+    pub fn from_bit_count(num_bits: usize, k: usize) -> BloomFilter {
+        BloomFilter::with_params(num_bits, k).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Deserialize a Bloom filter previously written by [`BloomFilter::to_bytes`].
+    ///
+    /// Returns [`BloomError::UnexpectedEof`] if `bytes` is truncated,
+    /// [`BloomError::Corrupt`] or [`BloomError::UnsupportedVersion`] if it isn't a filter
+    /// this build can read, and [`BloomError::UnsupportedHashScheme`] if it was written by a
+    /// build deriving items' bits differently, so that it can't be queried correctly.
+    pub fn from_bytes(bytes: &[u8]) -> Result<BloomFilter, BloomError> {
+        BloomFilter::from_bytes_for_scheme(bytes, HASH_SCHEME_VERSION)
+    }
+
+    /// Deserialize a Bloom filter like [`BloomFilter::from_bytes`], accepting only filters
+    /// written under `hash_scheme`.
+    fn from_bytes_for_scheme(bytes: &[u8], hash_scheme: u8) -> Result<BloomFilter, BloomError> {
+        let (header, raw) = parse_header(bytes, hash_scheme)?;
+        let mut bits = BitVec::from_slice(raw);
+        bits.truncate(header.num_bits);
+        let mut bloom = BloomFilter {
+            num_hash_functions: header.num_hash_functions,
+            bits,
+            seed: header.seed,
+            expected_elements: header.expected_elements,
+            target_false_positive_rate: header.target_false_positive_rate,
+            inserted: 0,
+            enhanced_double_hashing: header.enhanced_double_hashing,
+            hash_builder: DefaultHashBuilder::default(),
+            metrics: Recorder::default(),
+        };
+        bloom.estimate_inserted();
+        Ok(bloom)
+    }
+}
+
+impl<S: BuildHasher> BloomFilter<S>
+where
+    S::Hasher: 'static,
+{
+    /// Instantiate a new [`BloomFilter`] like [`BloomFilter::new`], hashing items with
+    /// hashers built by `hash_builder`.
+    ///
+    /// [`BloomFilter`]: BloomFilter
+    #[cfg(feature = "std")]
+    pub fn with_hasher(
+        num_elements: usize,
+        false_positive_rate: f32,
+        hash_builder: S,
+    ) -> BloomFilter<S> {
+        BloomFilter::with_sizing(num_elements, false_positive_rate as f64, hash_builder)
+    }
+
+    /// Instantiate a new [`BloomFilter`] like [`BloomFilter::with_params`], hashing items
+    /// with hashers built by `hash_builder`.
+    ///
+    /// Returns [`BloomError::InvalidParameter`] if `num_bits` or `num_hash_functions` is zero,
+    /// or `num_hash_functions` is over [`MAX_NUM_HASH_FUNCTIONS`].
+    ///
+    /// [`BloomFilter`]: BloomFilter
+    pub fn with_params_and_hasher(
+        num_bits: usize,
+        num_hash_functions: usize,
+        hash_builder: S,
+    ) -> Result<BloomFilter<S>, BloomError> {
+        if num_bits == 0 {
+            return Err(BloomError::InvalidParameter(
+                "num_bits must be greater than zero".into(),
+            ));
+        }
+        if num_hash_functions == 0 {
+            return Err(BloomError::InvalidParameter(
+                "num_hash_functions must be greater than zero".into(),
+            ));
+        }
+        if num_hash_functions > MAX_NUM_HASH_FUNCTIONS {
+            return Err(BloomError::InvalidParameter(alloc::format!(
+                "num_hash_functions must be at most {}, got {}",
+                MAX_NUM_HASH_FUNCTIONS,
+                num_hash_functions
+            )));
+        }
+
+        let (expected_elements, target_false_positive_rate) =
+            calc_design(num_bits, num_hash_functions);
+        Ok(BloomFilter {
+            num_hash_functions,
+            bits: bitvec![u8, Lsb0; 0; num_bits],
+            seed: 0,
+            expected_elements,
+            target_false_positive_rate,
+            inserted: 0,
+            enhanced_double_hashing: false,
+            hash_builder,
+            metrics: Recorder::default(),
+        })
+    }
+
+    /// Deserialize a Bloom filter like [`BloomFilter::from_bytes`], hashing items with
+    /// hashers built by `hash_builder`.
+    ///
+    /// The serialized form doesn't record the hasher, so `hash_builder` must build the same
+    /// hashers as the filter that was serialized did, otherwise lookups return wrong results.
+    pub fn from_bytes_with_hasher(
+        bytes: &[u8],
+        hash_builder: S,
+    ) -> Result<BloomFilter<S>, BloomError> {
+        let bloom = BloomFilter::from_bytes(bytes)?;
+        Ok(BloomFilter {
+            num_hash_functions: bloom.num_hash_functions,
+            bits: bloom.bits,
+            seed: bloom.seed,
+            expected_elements: bloom.expected_elements,
+            target_false_positive_rate: bloom.target_false_positive_rate,
+            inserted: bloom.inserted,
+            enhanced_double_hashing: bloom.enhanced_double_hashing,
+            hash_builder,
+            metrics: bloom.metrics,
+        })
+    }
+
+    /// Returns the bytes backing the Bloom filter's bit vector, without copying them, e.g.
+    /// to send the filter elsewhere.
+    ///
+    /// The bytes are packed as in [`BloomFilter::to_bytes`], bit `i` being bit `i % 8` of
+    /// byte `i / 8`, so together with [`BloomFilter::num_bits`] and
+    /// [`BloomFilter::num_hash_functions`] they can be loaded into a filter built with
+    /// [`BloomFilter::with_params`] by [`BloomFilter::merge_raw`]. They're
+    /// [`BloomFilter::size`] bytes long, so if `m` isn't a multiple of 8 the last byte
+    /// includes padding bits past the end of the filter, which are zero and are ignored by
+    /// [`BloomFilter::merge_raw`].
+    pub fn as_raw_slice(&self) -> &[u8] {
+        self.bits.as_raw_slice()
+    }
+}
+
+impl<S: BuildHasher, W: BitRegister + BitStore> BloomFilter<S, W>
+where
+    S::Hasher: 'static,
+{
+    /// Instantiate a new [`BloomFilter`] with any storage word, sized like
+    /// [`BloomFilter::with_hasher`].
+    ///
+    /// [`BloomFilter`]: BloomFilter
+    #[cfg(feature = "std")]
+    fn with_sizing(
+        num_elements: usize,
+        false_positive_rate: f64,
+        hash_builder: S,
+    ) -> BloomFilter<S, W> {
+        let (m, k) = calc_sizing(num_elements, false_positive_rate);
+        BloomFilter::with_geometry(m, k, num_elements, false_positive_rate, hash_builder)
+    }
 
+    /// Instantiate a new [`BloomFilter`] with any storage word, of `num_bits` bits and
+    /// `num_hash_functions` hash functions, designed for `num_elements` at
+    /// `false_positive_rate`.
+    ///
+    /// [`BloomFilter`]: BloomFilter
+    #[cfg(feature = "std")]
+    fn with_geometry(
+        num_bits: usize,
+        num_hash_functions: usize,
+        num_elements: usize,
+        false_positive_rate: f64,
+        hash_builder: S,
+    ) -> BloomFilter<S, W> {
         BloomFilter {
-            num_hash_functions: k,
-            bits: bitvec![u8, Lsb0; 0; m],
+            num_hash_functions,
+            bits: BitVec::repeat(false, num_bits),
+            seed: 0,
+            expected_elements: num_elements,
+            target_false_positive_rate: false_positive_rate,
+            inserted: 0,
+            enhanced_double_hashing: false,
+            hash_builder,
+            metrics: Recorder::default(),
         }
     }
 
+    /// Reset the Bloom filter to an empty filter sized for `num_elements` at
+    /// `false_positive_rate`, like a new one built by [`BloomFilter::new`] with the same seed
+    /// and hasher, but reusing the bit vector's allocation.
+    ///
+    /// The allocation is only grown if the new size in bits doesn't fit in it, so filters
+    /// that are rotated frequently can be pooled rather than reallocated each time.
+    #[cfg(feature = "std")]
+    pub fn reset_with(&mut self, num_elements: usize, false_positive_rate: f64) {
+        let (m, k) = calc_sizing(num_elements, false_positive_rate);
+        self.bits.clear();
+        self.bits.resize(m, false);
+        self.num_hash_functions = k;
+        self.expected_elements = num_elements;
+        self.target_false_positive_rate = false_positive_rate;
+        self.inserted = 0;
+    }
+
     /// Returns size in bytes of the Bloom filter's bit vector.
     pub fn size(&self) -> usize {
-        self.bits.len() / 8
+        self.bits.len().div_ceil(8)
     }
 
-    /// Insert an item into the Bloom filter.
+    /// Returns the number of hash functions, `k`.
+    pub fn num_hash_functions(&self) -> usize {
+        self.num_hash_functions
+    }
+
+    /// Returns the seed mixed into the hashes of every item, `0` if the filter is unseeded.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns whether the Bloom filter derives its bit indices by enhanced double hashing,
+    /// as set by [`BloomFilterBuilder::enhanced_double_hashing`], rather than by plain
+    /// double hashing.
+    pub fn enhanced_double_hashing(&self) -> bool {
+        self.enhanced_double_hashing
+    }
+
+    /// Returns the exact size in bits of the Bloom filter's bit vector, `m`.
+    pub fn num_bits(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Returns an estimate of the number of distinct items inserted into the Bloom filter.
     ///
-    /// To insert an item *`x`* into the Bloom filter, we first compute the *`k`* hash
-    /// functions on *`x`*, and for each resulting hash, set the corresponding slot of `A`
-    /// to 1.
-    pub fn insert<T: Hash>(&mut self, item: &T) {
-        for i in 0..self.num_hash_functions {
-            let b = self.calc_bit(item, i);
-            self.bits.set(b, true);
-        }
+    /// Given *`X`* set bits, the estimate is *`-(m/k)ln(1 - X/m)`*.
+    #[cfg(feature = "std")]
+    pub fn estimate_count(&self) -> usize {
+        self.estimate_count_from_ones(self.count_ones()).round() as usize
     }
 
-    /// Returns whether Bloom filter contains the item. It may return a false positive
-    /// but will never return a false negative.
+    /// Returns the number of inserts, lookups and hashes computed since the Bloom filter was
+    /// built, and the time spent on inserts and lookups, to profile the cost of hashing.
     ///
-    /// Computes *`k`* hash functions on *`x`*, and the first time one of the corresponding
-    /// slots of *`A`* equals `0`, the lookup reports the item as `Not Contained`; otherwise
-    /// it reports the item as `Contained`.
-    pub fn contains<T: Hash>(&self, item: &T) -> bool {
-        for i in 0..self.num_hash_functions {
-            if !(self.bits[self.calc_bit(item, i)]) {
-                return false;
-            }
+    /// Each item hashed costs two hashes whatever the number of hash functions, thanks to
+    /// double hashing, or one with a [`Sip128HashBuilder`]. The metrics aren't serialized,
+    /// so they restart at zero for a deserialized filter, and a clone carries on from the
+    /// original's.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.snapshot()
+    }
+
+    /// Returns the number of items the Bloom filter was designed to hold at its target false
+    /// positive rate: the `num_elements` it was built for, or for a filter built from its
+    /// size in bits and number of hash functions, the *`mln(2)/k`* items for which they're
+    /// optimal.
+    pub fn capacity(&self) -> usize {
+        self.expected_elements
+    }
+
+    /// Returns the number of elements the Bloom filter was designed for, the `num_elements`
+    /// passed to [`BloomFilter::new`]. Same as [`BloomFilter::capacity`].
+    pub fn expected_elements(&self) -> usize {
+        self.expected_elements
+    }
+
+    /// Returns the false positive rate the Bloom filter was designed for, the
+    /// `false_positive_rate` passed to [`BloomFilter::new`]. For a filter built from its size
+    /// in bits and number of hash functions, it's the rate they give at
+    /// [`capacity`](BloomFilter::capacity), *`2^-k`*.
+    pub fn target_false_positive_rate(&self) -> f64 {
+        self.target_false_positive_rate
+    }
+
+    /// Returns whether more items have been inserted than the Bloom filter's
+    /// [`capacity`](BloomFilter::capacity), past which its false positive rate climbs
+    /// quickly above its target.
+    ///
+    /// Inserts are counted when they set at least one new bit, so items inserted again, and
+    /// the occasional new item whose bits were all already set, aren't counted. The count
+    /// isn't serialized, so a deserialized filter's starts from
+    /// [`estimate_count`](BloomFilter::estimate_count), and one serialized past its capacity
+    /// is still saturated. Without `std` there's no estimate, and it starts from zero.
+    pub fn is_saturated(&self) -> bool {
+        self.inserted > self.expected_elements as u64
+    }
+
+    /// Returns an estimate of the number of distinct items inserted into the Bloom filter,
+    /// for parity with the standard collections.
+    ///
+    /// A Bloom filter doesn't record its items, so unlike [`BloomFilter::is_empty`] this is
+    /// only ever an estimate: it's the same as [`BloomFilter::estimate_count`].
+    #[cfg(feature = "std")]
+    pub fn len(&self) -> usize {
+        self.estimate_count()
+    }
+
+    /// Returns whether nothing has been inserted into the Bloom filter, which is exactly
+    /// when none of its bits are set.
+    pub fn is_empty(&self) -> bool {
+        self.bits.not_any()
+    }
+
+    /// Returns an estimate of the [Jaccard index](https://en.wikipedia.org/wiki/Jaccard_index)
+    /// of the sets of items inserted into this Bloom filter and `other`, the size of their
+    /// intersection over the size of their union, between `0.0` and `1.0`.
+    ///
+    /// The sizes of both sets and of their union are estimated like
+    /// [`BloomFilter::estimate_count`], the latter from the bits set in either filter, and
+    /// the size of the intersection follows by inclusion-exclusion. It's only an estimate,
+    /// and degrades as the filters fill up. Two empty filters have an index of `1.0`.
+    ///
+    /// Both filters must have the same size in bits, number of hash functions and seed, and
+    /// be designed for the same number of elements and false positive rate, otherwise
+    /// [`BloomError::MismatchedParameters`] is returned.
+    #[cfg(feature = "std")]
+    pub fn estimate_jaccard(&self, other: &BloomFilter<S, W>) -> Result<f64, BloomError> {
+        self.check_compatible(other)?;
+        let union = self.estimate_count_from_ones(self.count_union_ones(other));
+        if union == 0.0 {
+            return Ok(1.0);
         }
-        true
+        let a = self.estimate_count_from_ones(self.count_ones());
+        let b = other.estimate_count_from_ones(other.count_ones());
+        Ok(((a + b - union) / union).clamp(0.0, 1.0))
     }
 
-    /// Calculate index of bit for given item and hashing function number
-    fn calc_bit<T: Hash>(&self, item: &T, hash_func_num: usize) -> usize {
-        let mut hasher = SipHasher::new_with_keys(hash_func_num as u64, 0);
-        item.hash(&mut hasher);
-        hasher.finish() as usize % self.bits.len()
+    /// Returns an estimate of the number of distinct items inserted into either this Bloom
+    /// filter or `other`, without merging them.
+    ///
+    /// It's estimated like [`BloomFilter::estimate_count`] from the bits set in either
+    /// filter, so it equals the estimate of the filter [`BloomFilter::union`] would produce.
+    ///
+    /// Both filters must have the same size in bits, number of hash functions and seed, and
+    /// be designed for the same number of elements and false positive rate, otherwise
+    /// [`BloomError::MismatchedParameters`] is returned.
+    #[cfg(feature = "std")]
+    pub fn estimate_union_count(&self, other: &BloomFilter<S, W>) -> Result<usize, BloomError> {
+        self.check_compatible(other)?;
+        Ok(self
+            .estimate_count_from_ones(self.count_union_ones(other))
+            .round() as usize)
     }
-}
 
-/// Calculate the appropriate size in bits of the Bloom filter, `m`, given
-/// `n` and `f`, the expected number of elements contained in the Bloom filter and the
-/// target false positive rate, respectively.
-///
-/// *`(-nln(f))/ln(2)^2`*
-fn calc_m(n: usize, f: f32) -> usize {
-    // https://en.wikipedia.org/wiki/Bloom_filter#Optimal_number_of_hash_functions
-    (-f.ln() * (n as f32) / 2_f32.ln().powf(2_f32)) as usize
-}
+    /// Returns an estimate of the number of distinct items inserted into both this Bloom
+    /// filter and `other`, without intersecting them.
+    ///
+    /// It follows by inclusion-exclusion from the estimates of both filters' counts and of
+    /// [`BloomFilter::estimate_union_count`]. That's more accurate than the estimate of the
+    /// filter [`BloomFilter::intersect`] would produce, whose bits may have been set in each
+    /// filter by different items. Like [`BloomFilter::estimate_jaccard`], it degrades as the
+    /// filters fill up.
+    ///
+    /// Both filters must have the same size in bits, number of hash functions and seed, and
+    /// be designed for the same number of elements and false positive rate, otherwise
+    /// [`BloomError::MismatchedParameters`] is returned.
+    #[cfg(feature = "std")]
+    pub fn estimate_intersection_count(
+        &self,
+        other: &BloomFilter<S, W>,
+    ) -> Result<usize, BloomError> {
+        self.check_compatible(other)?;
+        let union = self.estimate_count_from_ones(self.count_union_ones(other));
+        let a = self.estimate_count_from_ones(self.count_ones());
+        let b = other.estimate_count_from_ones(other.count_ones());
+        Ok((a + b - union).max(0.0).round() as usize)
+    }
 
-/// Calculate the number of hash functions to use, `k`, given `n` and `m`, the expected
-/// number of elements contained in the Bloom filter and the size in bits of the Bloom
-/// filter.
-///
-/// *`(mln(2)/n)`*
-fn calc_k(n: usize, m: usize) -> usize {
-    // https://en.wikipedia.org/wiki/Bloom_filter#Optimal_number_of_hash_functions
-    ((m as f32 * 2_f32.ln()) / n as f32) as usize
-}
+    /// Returns an estimate of the number of distinct items inserted into this Bloom filter
+    /// but not into `earlier`, e.g. an earlier snapshot of it, to monitor its growth between
+    /// snapshots.
+    ///
+    /// It's the estimate of [`BloomFilter::estimate_union_count`] less that of `earlier`'s
+    /// count, so items inserted into `earlier` but not into this filter don't offset new
+    /// ones. It degrades as the filters fill up.
+    ///
+    /// Both filters must have the same size in bits, number of hash functions and seed, and
+    /// be designed for the same number of elements and false positive rate, otherwise
+    /// [`BloomError::MismatchedParameters`] is returned.
+    #[cfg(feature = "std")]
+    pub fn estimate_added_count(&self, earlier: &BloomFilter<S, W>) -> Result<usize, BloomError> {
+        self.check_compatible(earlier)?;
+        let union = self.estimate_count_from_ones(self.count_union_ones(earlier));
+        let before = earlier.estimate_count_from_ones(earlier.count_ones());
+        Ok((union - before).max(0.0).round() as usize)
+    }
 
-#[cfg(test)]
-mod tests {
-    use crate::*;
+    /// Returns the number of bit positions at which this Bloom filter and `other` differ, the
+    /// [Hamming distance](https://en.wikipedia.org/wiki/Hamming_distance) between their bit
+    /// vectors, on which similarity metrics can be built.
+    ///
+    /// Both filters must have the same size in bits, number of hash functions and seed, and
+    /// be designed for the same number of elements and false positive rate, otherwise
+    /// [`BloomError::MismatchedParameters`] is returned.
+    pub fn hamming_distance(&self, other: &BloomFilter<S, W>) -> Result<usize, BloomError> {
+        self.check_compatible(other)?;
+        Ok(self
+            .bits
+            .chunks(64)
+            .zip(other.bits.chunks(64))
+            .map(|(a, b)| (a.load_le::<u64>() ^ b.load_le::<u64>()).count_ones() as usize)
+            .sum())
+    }
 
-    fn check_sync<T: Sync>(_t: &T) {}
+    /// Returns the estimated false positive rate of the Bloom filter in its current state.
+    ///
+    /// Given *`X`* set bits, the rate is *`(X/m)^k`*: `0.0` for an empty filter, approaching
+    /// `1.0` as the filter saturates.
+    #[cfg(feature = "std")]
+    pub fn current_false_positive_rate(&self) -> f64 {
+        self.fill_ratio().powi(self.num_hash_functions as i32)
+    }
 
-    fn check_send<T: Send>(_t: &T) {}
+    /// Returns the false positive rate of the Bloom filter measured by querying `trials` items
+    /// drawn from `gen`, the fraction of them it contains, or `0.0` for no trials.
+    ///
+    /// `gen` must only yield items that weren't inserted, e.g. random numbers outside the
+    /// range of inserted ones, otherwise true positives inflate the rate. Unlike
+    /// [`BloomFilter::current_false_positive_rate`] this is an empirical measurement, useful
+    /// for verifying that a filter's configuration meets expectations.
+    pub fn measure_false_positive_rate<F: FnMut() -> u64>(&self, trials: usize, mut gen: F) -> f64 {
+        if trials == 0 {
+            return 0.0;
+        }
+        let false_positives = (0..trials).filter(|_| self.contains(&gen())).count();
+        false_positives as f64 / trials as f64
+    }
 
-    #[test]
-    fn m() {
-        let (n, f) = (1_000_000, 0.02);
-        assert_eq!(calc_m(n, f), 8_142_363);
+    /// Returns the fraction of bits currently set in the Bloom filter, between `0.0` and
+    /// `1.0`.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.bits.len() as f64
     }
 
-    #[test]
-    fn k() {
-        let (n, m) = (1_000_000, 8_142_363);
-        assert_eq!(calc_k(n, m), 5);
+    /// Returns the fill ratio at which the Bloom filter's
+    /// [`current_false_positive_rate`](BloomFilter::current_false_positive_rate) reaches its
+    /// [`target_false_positive_rate`](BloomFilter::target_false_positive_rate), *`f^(1/k)`*.
+    ///
+    /// For a filter with the optimal number of hash functions this is about `0.5`. Comparing
+    /// it against [`BloomFilter::fill_ratio`] tells when to rotate the filter.
+    #[cfg(feature = "std")]
+    pub fn target_fill_ratio(&self) -> f64 {
+        self.target_false_positive_rate
+            .powf(1.0 / self.num_hash_functions as f64)
     }
 
-    #[test]
-    fn size() {
-        let bloom = BloomFilter::new(100_000, 0.01);
-        assert_eq!(bloom.size(), 119813);
+    /// Returns an estimate of how many more distinct items can be inserted into the Bloom
+    /// filter before its
+    /// [`current_false_positive_rate`](BloomFilter::current_false_positive_rate) reaches
+    /// `target_fp_rate`, or `0` if it already has.
+    ///
+    /// It's the difference between the number of items that would fill the filter to
+    /// *`target_fp_rate^(1/k)`* and [`BloomFilter::estimate_count`], both estimated from set
+    /// bits.
+    #[cfg(feature = "std")]
+    pub fn remaining_capacity(&self, target_fp_rate: f64) -> usize {
+        let m = self.bits.len() as f64;
+        let k = self.num_hash_functions as f64;
+        let target_fill = target_fp_rate.powf(1.0 / k);
+        let target_count = -(m / k) * (1.0 - target_fill).ln();
+        // Negative when past the target and NaN for a NaN target, either of which is 0
+        (target_count - self.estimate_count_from_ones(self.count_ones()))
+            .max(0.0)
+            .round() as usize
     }
 
-    #[test]
-    fn contains_true() {
-        let mut bloom = BloomFilter::new(100_000, 0.01);
-        bloom.insert(&"hi");
-        assert!(bloom.contains(&"hi"));
+    /// Returns an iterator over the indices of the bits currently set, in increasing order.
+    ///
+    /// Useful for inspecting how saturated the filter is or diffing two filters.
+    pub fn set_bits(&self) -> impl Iterator<Item = usize> + '_ {
+        self.bits.iter_ones()
     }
 
-    #[test]
-    fn contains_false() {
-        let mut bloom = BloomFilter::new(100_000, 0.01);
-        bloom.insert(&"hi");
-        assert!(!bloom.contains(&"yo"));
+    /// Returns whether the bit at `index` is set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` isn't less than [`BloomFilter::num_bits`].
+    pub fn is_bit_set(&self, index: usize) -> bool {
+        self.bits[index]
+    }
+
+    /// Returns the indices of the bits the item maps to, without inserting it, sorted and
+    /// deduplicated, so at most *`k`* of them.
+    ///
+    /// Useful for debugging false positives by inspecting the overlap between items.
+    pub fn bit_indices<T: Hash + ?Sized>(&self, item: &T) -> Vec<usize> {
+        let (h1, h2) = self.hash_pair(item);
+        let mut indices: Vec<usize> = (0..self.num_hash_functions)
+            .map(|i| self.calc_bit(h1, h2, i))
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    /// Returns the number of the item's *`k`* bit indices that coincide with an earlier one,
+    /// `0` if they're all distinct.
+    ///
+    /// An item whose hash functions collide sets fewer than *`k`* bits, raising the false
+    /// positive rate slightly. This validates how often that happens for a sample of items,
+    /// e.g. to decide whether to build the filter with
+    /// [`BloomFilterBuilder::enhanced_double_hashing`].
+    pub fn index_collisions<T: Hash + ?Sized>(&self, item: &T) -> usize {
+        self.num_hash_functions - self.bit_indices(item).len()
+    }
+
+    /// Returns each of the item's *`k`* bit indices, in hash function order, paired with
+    /// whether that bit is set.
+    ///
+    /// Useful for debugging why an item that was never inserted is reported as present: if
+    /// every bit is set, these are the bits other items set that caused the false positive,
+    /// which [`BloomFilter::bit_indices`] of the inserted items can be matched against.
+    pub fn explain_contains<T: Hash + ?Sized>(&self, item: &T) -> Vec<(usize, bool)> {
+        let (h1, h2) = self.hash_pair(item);
+        (0..self.num_hash_functions)
+            .map(|i| {
+                let bit = self.calc_bit(h1, h2, i);
+                (bit, self.bits[bit])
+            })
+            .collect()
+    }
+
+    /// Insert an item into the Bloom filter.
+    ///
+    /// To insert an item *`x`* into the Bloom filter, we first compute the *`k`* hash
+    /// functions on *`x`*, and for each resulting hash, set the corresponding slot of `A`
+    /// to 1.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        self.insert_check(item);
+    }
+
+    /// Insert an item into the Bloom filter, returning whether it was probably new.
+    ///
+    /// Like [`HashSet::insert`], returns `true` if at least one of the item's bits flipped
+    /// from `0` to `1`, and `false` if all of them were already set, meaning the item was
+    /// probably already present.
+    ///
+    /// [`HashSet::insert`]: std::collections::HashSet::insert
+    pub fn insert_check<T: Hash + ?Sized>(&mut self, item: &T) -> bool {
+        self.insert_and_report(item) > 0
+    }
+
+    /// Insert an item into the Bloom filter, returning the number of bits that flipped from
+    /// `0` to `1`, between `0` and *`k`*.
+    ///
+    /// Summed over all inserts, this is the exact number of bits set in the filter, so it
+    /// tracks the filter's fill without scanning the whole bit vector as
+    /// [`BloomFilter::fill_ratio`] does.
+    pub fn insert_and_report<T: Hash + ?Sized>(&mut self, item: &T) -> usize {
+        let timer = self.metrics.start();
+        let (h1, h2) = self.hash_pair(item);
+        let flipped = self.insert_hashes_counting_bits(h1, h2);
+        self.metrics.record_insert(timer);
+        flipped
+    }
+
+    /// Insert an item into the Bloom filter given its two precomputed hashes, `h1` and `h2`,
+    /// from which its *`k`* bit indices are derived by double hashing.
+    ///
+    /// This skips hashing the item, e.g. when it was already hashed upstream or one hash
+    /// computation is shared by several filters. The hashes should be two independent,
+    /// uniformly distributed 64-bit hashes of the item, such as the outputs of two hashers
+    /// keyed differently, and every item must be hashed the same way each time.
+    ///
+    /// Items inserted this way are only found by [`BloomFilter::contains`], and vice versa,
+    /// if the hashes match those the filter derives itself: for an unseeded filter, `h1` is
+    /// the item's hash and `h2` the hash of the byte `1` followed by the item, both built by
+    /// the filter's hasher. Otherwise, query with [`BloomFilter::contains_hashes`].
+    pub fn insert_hashes(&mut self, h1: u64, h2: u64) {
+        let timer = self.metrics.start();
+        self.insert_hashes_counting_bits(h1, h2);
+        self.metrics.record_insert(timer);
+    }
+
+    /// Returns whether the Bloom filter contains an item given its two precomputed hashes,
+    /// `h1` and `h2`, as produced for [`BloomFilter::insert_hashes`]. It may return a false
+    /// positive but will never return a false negative.
+    pub fn contains_hashes(&self, h1: u64, h2: u64) -> bool {
+        let timer = self.metrics.start();
+        let found = self.contains_bits(h1, h2);
+        self.metrics.record_contains(timer);
+        found
+    }
+
+    /// Returns whether all the bits derived from `h1` and `h2` are set.
+    fn contains_bits(&self, h1: u64, h2: u64) -> bool {
+        (0..self.num_hash_functions).all(|i| self.bits[self.calc_bit(h1, h2, i)])
+    }
+
+    /// Set the bits derived from `h1` and `h2`, returning the number that flipped from `0`
+    /// to `1`.
+    fn insert_hashes_counting_bits(&mut self, h1: u64, h2: u64) -> usize {
+        let mut flipped = 0;
+        for i in 0..self.num_hash_functions {
+            let b = self.calc_bit(h1, h2, i);
+            if !self.bits.replace(b, true) {
+                flipped += 1;
+            }
+        }
+        if flipped > 0 {
+            self.inserted += 1;
+        }
+        flipped
+    }
+
+    /// Returns whether the Bloom filter already contained the item, inserting it if not.
+    ///
+    /// Equivalent to calling [`BloomFilter::contains`] followed by [`BloomFilter::insert`],
+    /// but only hashes the item once. As with `contains`, a return value of `true` may be a
+    /// false positive, in which case the item is never recorded as new.
+    pub fn contains_or_insert<T: Hash + ?Sized>(&mut self, item: &T) -> bool {
+        !self.insert_check(item)
+    }
+
+    /// Insert an item into the Bloom filter, calling `on_new` if it was probably new.
+    ///
+    /// Like [`BloomFilter::contains_or_insert`], this only hashes the item once, so it suits
+    /// deduplication pipelines that trigger a side effect the first time an item is seen.
+    /// `on_new` is called when at least one of the item's bits flipped from `0` to `1`, so a
+    /// false positive means it's skipped for an item that was in fact new.
+    pub fn get_or_insert_with<T: Hash + ?Sized>(&mut self, item: &T, on_new: impl FnOnce()) {
+        if self.insert_check(item) {
+            on_new();
+        }
+    }
+
+    /// Returns whether Bloom filter contains the item. It may return a false positive
+    /// but will never return a false negative.
+    ///
+    /// Computes *`k`* hash functions on *`x`*, and the first time one of the corresponding
+    /// slots of *`A`* equals `0`, the lookup reports the item as `Not Contained`; otherwise
+    /// it reports the item as `Contained`.
+    ///
+    /// Items are only told apart by their [`Hash`] implementations, so as with
+    /// [`HashSet`]'s [`Borrow`]-based lookups, an item can be queried through any form that
+    /// hashes like it. `item` may be unsized, and `String`, `&str` and `str` all hash
+    /// identically, so an item inserted as a `String` is found by `contains("hi")` or
+    /// `contains(&"hi")`. The same holds for `Vec<T>`, `&[T]` and `[T]`.
+    ///
+    /// [`HashSet`]: std::collections::HashSet
+    /// [`Borrow`]: core::borrow::Borrow
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        let timer = self.metrics.start();
+        let (h1, h2) = self.hash_pair(item);
+        let found = self.contains_bits(h1, h2);
+        self.metrics.record_contains(timer);
+        found
+    }
+
+    /// Insert a byte string into the Bloom filter, feeding the bytes to the hashers directly
+    /// rather than through [`Hash`].
+    ///
+    /// This saves the framing that [`Hash`] implementations add, such as the length prefix of
+    /// `[u8]` or the terminator byte of `str`, so it is *not* interchangeable with
+    /// [`BloomFilter::insert`]: `insert_bytes(b"hi")` and `insert(&"hi")` set different bits.
+    /// Query byte strings inserted this way with [`BloomFilter::contains_bytes`].
+    pub fn insert_bytes(&mut self, bytes: &[u8]) {
+        let timer = self.metrics.start();
+        let (h1, h2) = self.hash_pair_of_bytes(bytes);
+        self.insert_hashes_counting_bits(h1, h2);
+        self.metrics.record_insert(timer);
+    }
+
+    /// Returns whether the Bloom filter contains a byte string inserted with
+    /// [`BloomFilter::insert_bytes`]. It may return a false positive but will never return a
+    /// false negative.
+    pub fn contains_bytes(&self, bytes: &[u8]) -> bool {
+        let timer = self.metrics.start();
+        let (h1, h2) = self.hash_pair_of_bytes(bytes);
+        let found = self.contains_bits(h1, h2);
+        self.metrics.record_contains(timer);
+        found
+    }
+
+    /// Insert every item of a slice into the Bloom filter.
+    pub fn insert_all<T: Hash>(&mut self, items: &[T]) {
+        for item in items {
+            self.insert(item);
+        }
+    }
+
+    /// Insert every line read from `reader` into the Bloom filter, trimmed of surrounding
+    /// whitespace, returning the number of lines inserted. Lines that are empty once trimmed
+    /// are skipped.
+    ///
+    /// Lines are read one at a time into a reused buffer, so a large file, such as a word
+    /// list or a log, is never held in memory at once. Each line is inserted as a `&str`, so
+    /// it's found by `contains(line)`. Returns the first I/O error, or an
+    /// [`io::ErrorKind::InvalidData`] error for a line that isn't UTF-8, leaving the lines
+    /// before it inserted.
+    ///
+    /// [`io::ErrorKind::InvalidData`]: std::io::ErrorKind::InvalidData
+    #[cfg(feature = "std")]
+    pub fn insert_lines<R: std::io::BufRead>(&mut self, mut reader: R) -> std::io::Result<usize> {
+        let mut line = String::new();
+        let mut inserted = 0;
+        while reader.read_line(&mut line)? > 0 {
+            let item = line.trim();
+            if !item.is_empty() {
+                self.insert(item);
+                inserted += 1;
+            }
+            line.clear();
+        }
+        Ok(inserted)
+    }
+
+    /// Returns whether the Bloom filter contains every item of a slice. Like
+    /// [`BloomFilter::contains`], it may return a false positive but will never return a
+    /// false negative.
+    pub fn contains_all<T: Hash>(&self, items: &[T]) -> bool {
+        items.iter().all(|item| self.contains(item))
+    }
+
+    /// Returns whether the Bloom filter contains any item of a slice, stopping at the first
+    /// one found. Like [`BloomFilter::contains`], it may return a false positive but will
+    /// never return a false negative.
+    pub fn contains_any<T: Hash>(&self, items: &[T]) -> bool {
+        items.iter().any(|item| self.contains(item))
+    }
+
+    /// Returns the number of items of a slice the Bloom filter contains, without allocating
+    /// a result per item. False positives can make it exceed the number actually inserted,
+    /// by about the false positive rate times the number of items that weren't, but never
+    /// fall short of it.
+    pub fn count_contained<T: Hash>(&self, items: &[T]) -> usize {
+        items.iter().filter(|item| self.contains(item)).count()
+    }
+
+    /// Returns whether the Bloom filter contains each of `items`, in order, to screen a list
+    /// of candidates in one pass. Like [`BloomFilter::contains`], each result may be a false
+    /// positive but will never be a false negative.
+    pub fn contains_each<'a, T, I>(&self, items: I) -> Vec<bool>
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: Hash + 'a,
+    {
+        items.into_iter().map(|item| self.contains(item)).collect()
+    }
+
+    /// Reset the Bloom filter to its empty state, keeping its parameters and allocation.
+    pub fn clear(&mut self) {
+        self.bits.fill(false);
+        self.inserted = 0;
+    }
+
+    /// Merge `other` into this Bloom filter, so that it reports every item contained in
+    /// either filter.
+    ///
+    /// Both filters must have the same size in bits, number of hash functions and seed, and
+    /// be designed for the same number of elements and false positive rate, otherwise
+    /// [`BloomError::MismatchedParameters`] is returned and `self` is left unchanged.
+    pub fn union(&mut self, other: &BloomFilter<S, W>) -> Result<(), BloomError> {
+        self.check_compatible(other)?;
+        for (a, b) in self
+            .bits
+            .as_raw_mut_slice()
+            .iter_mut()
+            .zip(other.bits.as_raw_slice())
+        {
+            *a |= *b;
+        }
+        // Merging whole words must not set bits of the last word past `m`
+        self.bits.set_uninitialized(false);
+        self.inserted = self.inserted.saturating_add(other.inserted);
+        Ok(())
+    }
+
+    /// Merge `other` into this Bloom filter like [`BloomFilter::union`], allowing `other` to
+    /// be smaller, e.g. to fold a small filter into a larger one.
+    ///
+    /// `other`'s size in bits must divide this filter's, and both must have the same number
+    /// of hash functions and seed and derive bit indices the same way, otherwise
+    /// [`BloomError::MismatchedParameters`] is returned and `self` is left unchanged. They may
+    /// be designed for different numbers of elements and false positive rates.
+    ///
+    /// An item's bit in `other` is its hash modulo `other`'s size, *`m'`*, and its bit in
+    /// this filter, of size *`m = cm'`*, is the same hash modulo *`m`*, which is congruent to
+    /// it modulo *`m'`*. It's unknown which of the *`c`* congruent bits that is, so each bit
+    /// set in `other` is tiled across all of them. Every item contained in either filter is
+    /// then contained in this one, but the bits of `other` take up *`c`* times as many of this
+    /// filter's, so its false positive rate rises faster than after a union of equally sized
+    /// filters.
+    pub fn union_compatible(&mut self, other: &BloomFilter<S, W>) -> Result<(), BloomError> {
+        let (m, small_m) = (self.bits.len(), other.bits.len());
+        if !m.is_multiple_of(small_m)
+            || self.num_hash_functions != other.num_hash_functions
+            || self.seed != other.seed
+            || self.enhanced_double_hashing != other.enhanced_double_hashing
+        {
+            return Err(BloomError::MismatchedParameters);
+        }
+
+        for i in other.bits.iter_ones() {
+            for tile in (i..m).step_by(small_m) {
+                self.bits.set(tile, true);
+            }
+        }
+        self.inserted = self.inserted.saturating_add(other.inserted);
+        Ok(())
+    }
+
+    /// Merge all of `filters` into a new Bloom filter, like [`BloomFilter::union`] applied to
+    /// a copy of the first, e.g. to combine the filters built by sharded workers.
+    ///
+    /// Returns [`BloomError::InvalidParameter`] if `filters` is empty and
+    /// [`BloomError::MismatchedParameters`] if they aren't all compatible with each other.
+    pub fn union_all(filters: &[BloomFilter<S, W>]) -> Result<BloomFilter<S, W>, BloomError>
+    where
+        S: Clone,
+    {
+        let (first, rest) = filters
+            .split_first()
+            .ok_or_else(|| BloomError::InvalidParameter("filters must not be empty".into()))?;
+        // Check up front so that no copy is made of incompatible filters
+        for filter in rest {
+            first.check_compatible(filter)?;
+        }
+
+        let mut union = first.clone();
+        for filter in rest {
+            union.union(filter)?;
+        }
+        Ok(union)
+    }
+
+    /// Split the Bloom filter's bit array into `shards` pieces, the inverse of
+    /// [`BloomFilter::union_all`] for filters too large for one node: shard `i` holds bits
+    /// `i * shard_len` up to `(i + 1) * shard_len` of this filter, where `shard_len` is
+    /// `num_bits` divided by `shards`, rounded up. Every shard but the last is `shard_len`
+    /// bits long, and the last holds the rest. Shards keep this filter's number of hash
+    /// functions, seed, hasher and design, but start with an insert count of zero.
+    ///
+    /// A shard's own [`BloomFilter::contains`] hashes items modulo the shard's size, so it
+    /// doesn't find this filter's items. Queries have to be routed by this filter's bit
+    /// indices instead: bit `b` of [`BloomFilter::bit_indices`] lives in shard
+    /// `b / shard_len`, as its bit `b % shard_len`, and an item is contained if that bit is
+    /// [set](BloomFilter::is_bit_set) in its shard for each of its bits. Computing the
+    /// indices only needs a filter of the original size, number of hash functions and seed,
+    /// not its bits, so e.g. an empty one built with [`BloomFilter::with_params`] will do.
+    ///
+    /// ```
+    /// use bloom::BloomFilter;
+    ///
+    /// let mut bloom = BloomFilter::new(1_000, 0.01);
+    /// bloom.insert(&"hi");
+    /// let shards = bloom.split(4);
+    /// let shard_len = bloom.num_bits().div_ceil(4);
+    ///
+    /// let contains = |item: &str| {
+    ///     bloom
+    ///         .bit_indices(item)
+    ///         .into_iter()
+    ///         .all(|b| shards[b / shard_len].is_bit_set(b % shard_len))
+    /// };
+    /// assert!(contains("hi"));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero or leaves a shard with no bits.
+    pub fn split(&self, shards: usize) -> Vec<BloomFilter<S, W>>
+    where
+        S: Clone,
+    {
+        assert!(shards > 0, "shards must be greater than zero");
+        let m = self.bits.len();
+        let shard_len = m.div_ceil(shards);
+        assert!(
+            (shards - 1) * shard_len < m,
+            "{} shards of {} bits leave a shard with no bits",
+            shards,
+            m
+        );
+
+        self.bits
+            .chunks(shard_len)
+            .map(|chunk| {
+                let mut bits = BitVec::repeat(false, chunk.len());
+                bits.copy_from_bitslice(chunk);
+                BloomFilter {
+                    num_hash_functions: self.num_hash_functions,
+                    bits,
+                    seed: self.seed,
+                    expected_elements: self.expected_elements,
+                    target_false_positive_rate: self.target_false_positive_rate,
+                    inserted: 0,
+                    enhanced_double_hashing: self.enhanced_double_hashing,
+                    hash_builder: self.hash_builder.clone(),
+                    metrics: Recorder::default(),
+                }
+            })
+            .collect()
+    }
+
+    /// Intersect this Bloom filter with `other`, so that it only reports items contained in
+    /// both filters.
+    ///
+    /// Note that the result can have a higher false positive rate than a filter built
+    /// directly from the intersection of the two sets: a bit may be set in both filters by
+    /// different items, so an item present in only one of the sets can still be reported as
+    /// contained.
+    ///
+    /// Both filters must have the same size in bits, number of hash functions and seed, and
+    /// be designed for the same number of elements and false positive rate, otherwise
+    /// [`BloomError::MismatchedParameters`] is returned and `self` is left unchanged.
+    pub fn intersect(&mut self, other: &BloomFilter<S, W>) -> Result<(), BloomError> {
+        self.check_compatible(other)?;
+        for (a, b) in self
+            .bits
+            .as_raw_mut_slice()
+            .iter_mut()
+            .zip(other.bits.as_raw_slice())
+        {
+            *a &= *b;
+        }
+        self.bits.set_uninitialized(false);
+        Ok(())
+    }
+
+    /// Returns a smaller copy of the Bloom filter, `factor` times smaller, by repeatedly
+    /// folding its bit array in half and OR-ing the halves together.
+    ///
+    /// Bit `i` of the copy is set if any bit congruent to `i` modulo its new size was set,
+    /// which is exactly where an item's indices land in the smaller filter, so every item
+    /// contained in this filter is still contained in the copy. The price is a higher false
+    /// positive rate, as the copy has the same number of items in fewer bits. It is designed
+    /// for `factor` times fewer elements at the same target false positive rate.
+    ///
+    /// Returns [`BloomError::InvalidParameter`] unless `factor` is a power of two that divides
+    /// the size in bits.
+    pub fn downsample(&self, factor: usize) -> Result<BloomFilter<S, W>, BloomError>
+    where
+        S: Clone,
+    {
+        if !factor.is_power_of_two() || !self.bits.len().is_multiple_of(factor) {
+            return Err(BloomError::InvalidParameter(alloc::format!(
+                "factor must be a power of two dividing num_bits, got {}",
+                factor
+            )));
+        }
+
+        let num_bits = self.bits.len() / factor;
+        let mut bits = BitVec::repeat(false, num_bits);
+        for i in self.bits.iter_ones() {
+            bits.set(i % num_bits, true);
+        }
+        Ok(BloomFilter {
+            num_hash_functions: self.num_hash_functions,
+            bits,
+            seed: self.seed,
+            expected_elements: self.expected_elements / factor,
+            target_false_positive_rate: self.target_false_positive_rate,
+            inserted: self.inserted,
+            enhanced_double_hashing: self.enhanced_double_hashing,
+            hash_builder: self.hash_builder.clone(),
+            metrics: Recorder::default(),
+        })
+    }
+
+    /// Merge a raw bit array into this Bloom filter, like [`BloomFilter::union`] with a
+    /// filter holding those bits, without constructing one.
+    ///
+    /// `bits` is packed as in [`BloomFilter::to_bytes`], bit `i` being bit `i % 8` of byte
+    /// `i / 8`, and must come from a filter with `num_bits` bits and `num_hash_functions` hash
+    /// functions. Both must match this filter, and `bits` must be [`BloomFilter::size`] bytes
+    /// long, otherwise [`BloomError::MismatchedParameters`] is returned and `self` is left
+    /// unchanged. The seed can't be checked, so it's up to the caller to merge only bits
+    /// from filters seeded like this one.
+    pub fn merge_raw(
+        &mut self,
+        bits: &[u8],
+        num_bits: usize,
+        num_hash_functions: usize,
+    ) -> Result<(), BloomError> {
+        if num_bits != self.bits.len()
+            || num_hash_functions != self.num_hash_functions
+            || bits.len() != self.size()
+        {
+            return Err(BloomError::MismatchedParameters);
+        }
+
+        for (chunk, bytes) in self.bits.chunks_mut(64).zip(bits.chunks(8)) {
+            let mut word = [0; 8];
+            word[..bytes.len()].copy_from_slice(bytes);
+            chunk.store_le(chunk.load_le::<u64>() | u64::from_le_bytes(word));
+        }
+        Ok(())
+    }
+
+    /// Serialize the Bloom filter into a compact binary format.
+    ///
+    /// The output is a header holding a magic number, the format version, the size in bits
+    /// `m`, the number of hash functions `k`, the seed, the design and the version of the
+    /// scheme deriving items' bits from their hashes, followed by the packed bit array. It
+    /// can be loaded back with [`BloomFilter::from_bytes`].
+    ///
+    /// The format doesn't depend on the host, so filters can be moved between architectures.
+    /// Every multi-byte field is little-endian:
+    ///
+    /// | Offset | Size | Field                                                    |
+    /// |--------|------|----------------------------------------------------------|
+    /// | 0      | 4    | [`MAGIC`]                                                |
+    /// | 4      | 4    | [`FORMAT_VERSION`], `u32`                                |
+    /// | 8      | 8    | `m`, `u64`                                               |
+    /// | 16     | 8    | `k`, `u64`                                               |
+    /// | 24     | 8    | seed, `u64`                                              |
+    /// | 32     | 8    | expected number of elements, `u64`                       |
+    /// | 40     | 8    | target false positive rate, `f64`                        |
+    /// | 48     | 1    | hash scheme, with the high bit set for enhanced hashing  |
+    /// | 49     | ⌈m/8⌉ | bit array                                               |
+    ///
+    /// The bit array is in [`Lsb0`] order whatever the storage word: bit `i` of the filter
+    /// is bit `i % 8`, counting from the least significant, of byte `i / 8`. Bits of the last
+    /// byte past `m` are zero.
+    ///
+    /// [`Lsb0`]: bitvec::order::Lsb0
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.size());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.bits.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.num_hash_functions as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&(self.expected_elements as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.target_false_positive_rate.to_le_bytes());
+        bytes.push(hash_scheme(self.enhanced_double_hashing));
+        // Bit `i` is bit `i % 8` of byte `i / 8`, whatever the storage word
+        for chunk in self.bits.chunks(64) {
+            bytes.extend_from_slice(&chunk.load_le::<u64>().to_le_bytes());
+        }
+        bytes.truncate(HEADER_LEN + self.size());
+        bytes
+    }
+
+    /// Check that `other` has the same size in bits, number of hash functions, seed and way
+    /// of deriving bit indices, and was designed for the same number of elements and false
+    /// positive rate.
+    fn check_compatible(&self, other: &BloomFilter<S, W>) -> Result<(), BloomError> {
+        if self.bits.len() != other.bits.len()
+            || self.num_hash_functions != other.num_hash_functions
+            || self.seed != other.seed
+            || self.enhanced_double_hashing != other.enhanced_double_hashing
+            || self.expected_elements != other.expected_elements
+            // Filters are sized from an `f32` rate, so rates equal as `f32` are the same
+            || self.target_false_positive_rate as f32 != other.target_false_positive_rate as f32
+        {
+            return Err(BloomError::MismatchedParameters);
+        }
+        Ok(())
+    }
+
+    /// Count the bits set in the filter.
+    ///
+    /// Storage words narrower than 64 bits are packed into `u64`s so that each popcount
+    /// covers 64 bits, which with the default byte storage is several times faster than
+    /// counting word by word. Bits of the last word past `m` are masked out.
+    fn count_ones(&self) -> usize {
+        let word_bits = bitvec::mem::bits_of::<W>();
+        let raw = self.bits.as_raw_slice();
+        let chunks = raw.chunks_exact((u64::BITS as usize / word_bits).max(1));
+        let tail: usize = chunks
+            .remainder()
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum();
+        let packed: usize = chunks
+            .map(|chunk| {
+                // The shift wraps to 0 for 64-bit words, which come one per chunk
+                chunk
+                    .iter()
+                    .fold(0_u64, |acc, word| {
+                        acc.wrapping_shl(word_bits as u32) | word.as_u64()
+                    })
+                    .count_ones() as usize
+            })
+            .sum();
+        let padding = BitSlice::<W, Lsb0>::from_slice(raw)[self.bits.len()..].count_ones();
+        packed + tail - padding
+    }
+
+    /// Count the bits set in either this filter or `other`, which must be the same size.
+    /// Bits of the last word past `m` are masked out, like in [`BloomFilter::count_ones`].
+    #[cfg(feature = "std")]
+    fn count_union_ones(&self, other: &BloomFilter<S, W>) -> usize {
+        let (a, b) = (self.bits.as_raw_slice(), other.bits.as_raw_slice());
+        let ones: usize = a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| (*a | *b).count_ones() as usize)
+            .sum();
+        let padding = match (a.last(), b.last()) {
+            (Some(a), Some(b)) => {
+                let last = [*a | *b];
+                let live = (self.bits.len() - 1) % bitvec::mem::bits_of::<W>() + 1;
+                BitSlice::<W, Lsb0>::from_slice(&last)[live..].count_ones()
+            }
+            _ => 0,
+        };
+        ones - padding
+    }
+
+    /// Estimate the number of distinct items that would set `ones` of the filter's bits.
+    #[cfg(feature = "std")]
+    fn estimate_count_from_ones(&self, ones: usize) -> f64 {
+        estimate_items(self.bits.len(), self.num_hash_functions, ones)
+    }
+
+    /// Calculate index of bit for given item hashes and hashing function number.
+    fn calc_bit(&self, h1: u64, h2: u64, hash_func_num: usize) -> usize {
+        if self.enhanced_double_hashing {
+            calc_index_enhanced(h1, h2, hash_func_num, self.bits.len())
+        } else {
+            calc_index(h1, h2, hash_func_num, self.bits.len())
+        }
+    }
+
+    /// Compute the two hashes of an item from which all `k` bit indices are derived.
+    fn hash_pair<T: Hash + ?Sized>(&self, item: &T) -> (u64, u64) {
+        self.metrics.record_hashes(hashes_per_item::<S>());
+        hash_pair(&self.hash_builder, self.seed, item)
+    }
+
+    /// Compute the two hashes of a byte string inserted with [`BloomFilter::insert_bytes`].
+    fn hash_pair_of_bytes(&self, bytes: &[u8]) -> (u64, u64) {
+        self.metrics.record_hashes(hashes_per_item::<S>());
+        hash_pair_with(&self.hash_builder, self.seed, |h| h.write(bytes))
+    }
+}
+
+impl<S, W: BitRegister + BitStore> BloomFilter<S, W> {
+    /// Start the count of inserts of a filter whose count wasn't recorded, such as a
+    /// deserialized one, from the estimate of its items, or from zero without `std`.
+    fn estimate_inserted(&mut self) {
+        #[cfg(feature = "std")]
+        {
+            let ones = self.bits.count_ones();
+            self.inserted =
+                estimate_items(self.bits.len(), self.num_hash_functions, ones).round() as u64;
+        }
+    }
+}
+
+impl<S, W: BitRegister + BitStore> PartialEq for BloomFilter<S, W> {
+    fn eq(&self, other: &Self) -> bool {
+        self.num_hash_functions == other.num_hash_functions
+            && self.seed == other.seed
+            && self.enhanced_double_hashing == other.enhanced_double_hashing
+            && self.bits == other.bits
+    }
+}
+
+impl<S, W: BitRegister + BitStore> Eq for BloomFilter<S, W> {}
+
+/// Summarizes the filter's parameters and fill rather than dumping its bits, which would be
+/// enormous for any realistically sized filter.
+impl<S, W: BitRegister + BitStore> fmt::Debug for BloomFilter<S, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BloomFilter")
+            .field("num_hash_functions", &self.num_hash_functions)
+            .field("num_bits", &self.bits.len())
+            .field("seed", &self.seed)
+            .field("size", &self.bits.len().div_ceil(8))
+            .field(
+                "fill_ratio",
+                &(self.bits.count_ones() as f64 / self.bits.len() as f64),
+            )
+            .finish()
+    }
+}
+
+/// Summarizes the Bloom filter for logs, e.g. `BloomFilter(m=958512 bits, k=7, fill=12.3%,
+/// ~50000 items)`: its size in bits, number of hash functions, fill ratio and
+/// [estimated](BloomFilter::estimate_count) number of items.
+#[cfg(feature = "std")]
+impl<S: BuildHasher, W: BitRegister + BitStore> fmt::Display for BloomFilter<S, W>
+where
+    S::Hasher: 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "BloomFilter(m={} bits, k={}, fill={:.1}%, ~{} items)",
+            self.bits.len(),
+            self.num_hash_functions,
+            self.fill_ratio() * 100.0,
+            self.estimate_count()
+        )
+    }
+}
+
+/// Creates an empty [`BloomFilter`] sized for 1000 elements at a false positive rate of
+/// `0.01`, like `BloomFilter::new(1000, 0.01)`.
+///
+/// [`BloomFilter`]: BloomFilter
+#[cfg(feature = "std")]
+impl Default for BloomFilter {
+    fn default() -> Self {
+        BloomFilter::new(DEFAULT_NUM_ELEMENTS, DEFAULT_FALSE_POSITIVE_RATE)
+    }
+}
+
+/// Builds a [`BloomFilter`] from an iterator.
+///
+/// The filter is sized for the iterator's size hint (its upper bound if known, otherwise its
+/// lower bound), but for no fewer than 1000 elements, at a false positive rate of `0.01`.
+/// Use [`BloomFilter::new`] and [`Extend`] instead when the number of elements isn't
+/// reflected in the size hint.
+///
+/// [`BloomFilter`]: BloomFilter
+#[cfg(feature = "std")]
+impl<T: Hash, S: BuildHasher + Default> FromIterator<T> for BloomFilter<S>
+where
+    S::Hasher: 'static,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let (lower, upper) = iter.size_hint();
+        let num_elements = upper.unwrap_or(lower).max(FROM_ITER_MIN_ELEMENTS);
+
+        let mut bloom =
+            BloomFilter::with_hasher(num_elements, FROM_ITER_FALSE_POSITIVE_RATE, S::default());
+        bloom.extend(iter);
+        bloom
+    }
+}
+
+/// Inserts every item of an iterator into a [`BloomFilter`].
+///
+/// [`BloomFilter`]: BloomFilter
+impl<T: Hash, S: BuildHasher, W: BitRegister + BitStore> Extend<T> for BloomFilter<S, W>
+where
+    S::Hasher: 'static,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(&item);
+        }
+    }
+}
+
+/// Compute the two independent hashes of an item from which all `k` indices are derived.
+/// The second hasher is fed a prefix byte so it diverges from the first. A nonzero `seed` is
+/// fed to both hashers first, keying them.
+///
+/// If `S` builds 128-bit SipHashers, as [`Sip128HashBuilder`] does, the item is only fed to
+/// one hasher and the two hashes are the halves of its 128-bit output.
+fn hash_pair<S: BuildHasher, T: Hash + ?Sized>(hash_builder: &S, seed: u64, item: &T) -> (u64, u64)
+where
+    S::Hasher: 'static,
+{
+    hash_pair_with(hash_builder, seed, |hasher| item.hash(hasher))
+}
+
+/// Compute the two hashes like [`hash_pair`], with `feed` writing the item to each hasher.
+fn hash_pair_with<S: BuildHasher>(
+    hash_builder: &S,
+    seed: u64,
+    feed: impl Fn(&mut S::Hasher),
+) -> (u64, u64)
+where
+    S::Hasher: 'static,
+{
+    let mut hasher1 = hash_builder.build_hasher();
+    if seed != 0 {
+        hasher1.write_u64(seed);
+    }
+    feed(&mut hasher1);
+    if let Some(hasher) = (&hasher1 as &dyn Any).downcast_ref::<Sip128Hasher>() {
+        let hash = hasher.finish128();
+        return (hash.h1, hash.h2);
+    }
+
+    let mut hasher2 = hash_builder.build_hasher();
+    if seed != 0 {
+        hasher2.write_u64(seed);
+    }
+    hasher2.write_u8(1);
+    feed(&mut hasher2);
+
+    (hasher1.finish(), hasher2.finish())
+}
+
+/// Returns the number of hashes [`hash_pair`] computes per item for hashers built by `S`.
+fn hashes_per_item<S: BuildHasher>() -> u64
+where
+    S::Hasher: 'static,
+{
+    if TypeId::of::<S::Hasher>() == TypeId::of::<Sip128Hasher>() {
+        1
+    } else {
+        2
+    }
+}
+
+/// The fields of a serialized filter's header, parsed by [`parse_header`].
+struct Header {
+    num_bits: usize,
+    num_hash_functions: usize,
+    seed: u64,
+    expected_elements: usize,
+    target_false_positive_rate: f64,
+    enhanced_double_hashing: bool,
+}
+
+/// Parse the header of a filter serialized by [`BloomFilter::to_bytes`], accepting only
+/// filters written under `hash_scheme`, returning it along with the packed bit array that
+/// follows, checked to be exactly as long as the header says.
+fn parse_header(bytes: &[u8], hash_scheme: u8) -> Result<(Header, &[u8]), BloomError> {
+    if bytes.len() < HEADER_LEN_V1 {
+        return Err(BloomError::UnexpectedEof);
+    }
+    if &bytes[..4] != MAGIC {
+        return Err(BloomError::Corrupt);
+    }
+    let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    let header_len = match version {
+        // Filters from before seeds were introduced are unseeded
+        1 => HEADER_LEN_V1,
+        // Filters from before their design was recorded get the one `m` and `k` are
+        // optimal for
+        2 => HEADER_LEN_V2,
+        3 => HEADER_LEN_V3,
+        FORMAT_VERSION => HEADER_LEN,
+        _ => return Err(BloomError::UnsupportedVersion(version)),
+    };
+    if bytes.len() < header_len {
+        return Err(BloomError::UnexpectedEof);
+    }
+    let (header, raw) = bytes.split_at(header_len);
+    // Filters from before the hash scheme was recorded were written under the first one
+    let written_scheme = header.get(48).copied().unwrap_or(1);
+    if written_scheme & !ENHANCED_DOUBLE_HASHING != hash_scheme {
+        return Err(BloomError::UnsupportedHashScheme(written_scheme));
+    }
+    let seed = match header.get(24..32) {
+        Some(seed) => u64::from_le_bytes(seed.try_into().unwrap()),
+        None => 0,
+    };
+    let m = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    let k = u64::from_le_bytes(header[16..24].try_into().unwrap());
+    let m = usize::try_from(m).map_err(|_| BloomError::Corrupt)?;
+    let k = usize::try_from(k).map_err(|_| BloomError::Corrupt)?;
+    if m == 0 || k == 0 || k > MAX_NUM_HASH_FUNCTIONS {
+        return Err(BloomError::Corrupt);
+    }
+    let (expected_elements, target_false_positive_rate) = match header.get(32..48) {
+        Some(design) => {
+            let n = u64::from_le_bytes(design[..8].try_into().unwrap());
+            let f = f64::from_le_bytes(design[8..].try_into().unwrap());
+            let n = usize::try_from(n).map_err(|_| BloomError::Corrupt)?;
+            if !(0.0..1.0).contains(&f) {
+                return Err(BloomError::Corrupt);
+            }
+            (n, f)
+        }
+        None => calc_design(m, k),
+    };
+
+    let num_bytes = m.div_ceil(8);
+    if raw.len() < num_bytes {
+        return Err(BloomError::UnexpectedEof);
+    }
+    if raw.len() > num_bytes {
+        return Err(BloomError::Corrupt);
+    }
+    // Padding bits past `m` are written as zero, and would be counted as set otherwise
+    if m % 8 != 0 && raw[num_bytes - 1] >> (m % 8) != 0 {
+        return Err(BloomError::Corrupt);
+    }
+
+    let header = Header {
+        num_bits: m,
+        num_hash_functions: k,
+        seed,
+        expected_elements,
+        target_false_positive_rate,
+        enhanced_double_hashing: written_scheme & ENHANCED_DOUBLE_HASHING != 0,
+    };
+    Ok((header, raw))
+}
+
+/// Returns the hash scheme recorded in a serialized filter: [`HASH_SCHEME_VERSION`], flagged
+/// if its bit indices are derived by enhanced double hashing.
+fn hash_scheme(enhanced_double_hashing: bool) -> u8 {
+    if enhanced_double_hashing {
+        HASH_SCHEME_VERSION | ENHANCED_DOUBLE_HASHING
+    } else {
+        HASH_SCHEME_VERSION
+    }
+}
+
+/// Calculate the `hash_func_num`-th index into `len` slots from an item's two hashes.
+///
+/// Uses the Kirsch-Mitzenmacher double hashing technique, deriving the `i`-th hash
+/// function as *`h1 + i*h2`*. The modulus is taken in `u64` so the full hash is used even
+/// where `usize` is 32 bits wide.
+///
+/// `h2` is forced odd: filter sizes are multiples of 8, and an even `h2` would confine all
+/// of an item's indices to a fraction of the slots.
+///
+/// `len` must be nonzero. Every constructor, and deserialization, rejects filters without
+/// bits, so a zero `len` is a bug caught here in debug builds rather than by a division by
+/// zero.
+fn calc_index(h1: u64, h2: u64, hash_func_num: usize, len: usize) -> usize {
+    debug_assert!(len > 0, "bit index into a filter without bits");
+    let hash = h1.wrapping_add((hash_func_num as u64).wrapping_mul(h2 | 1));
+    (hash % len as u64) as usize
+}
+
+/// Calculate the `hash_func_num`-th index into `len` slots from an item's two hashes by
+/// enhanced double hashing.
+///
+/// Dillinger and Manolios' enhanced double hashing derives the `i`-th hash function as
+/// *`h1 + i*h2 + (i^3 - i)/6`*. The cubic term sets indices apart even when *`i*h2`* cycles
+/// through few slots, as it does when `h2` shares a large factor with `len`, where plain
+/// double hashing maps several of an item's hash functions to the same slot. `h2` is used as
+/// is, since the cubic term alone keeps the indices from collapsing.
+fn calc_index_enhanced(h1: u64, h2: u64, hash_func_num: usize, len: usize) -> usize {
+    debug_assert!(len > 0, "bit index into a filter without bits");
+    let i = hash_func_num as u64;
+    let cubic = i.wrapping_mul(i).wrapping_mul(i).wrapping_sub(i) / 6;
+    let hash = h1.wrapping_add(i.wrapping_mul(h2)).wrapping_add(cubic);
+    (hash % len as u64) as usize
+}
+
+/// Returns the optimal size in bits, `m`, and number of hash functions, `k`, of a Bloom
+/// filter expected to contain `num_elements` items with a target `false_positive_rate`,
+/// without allocating one.
+///
+/// *`m = (-nln(f))/ln(2)^2`*, but at least 8 bits so that a filter for tiny `n` or `f` close
+/// to 1 is never empty, and *`k = mln(2)/n`*, but at least 1. [`BloomFilter::new`] rounds
+/// `m` up to a whole number of bytes.
+///
+/// ```
+/// let (m, k) = bloom::optimal_params(1_000_000, 0.02);
+/// assert_eq!((m, k), (8_142_363, 5));
+/// ```
+#[cfg(feature = "std")]
+pub fn optimal_params(num_elements: usize, false_positive_rate: f64) -> (usize, usize) {
+    // https://en.wikipedia.org/wiki/Bloom_filter#Optimal_number_of_hash_functions
+    // Computed in `f64`, as `f32` loses enough precision for large `n` to undersize the filter
+    let m = ((-false_positive_rate.ln() * num_elements as f64 / LN_2.powi(2)) as usize)
+        .max(MIN_NUM_BITS);
+    (m, calc_k(num_elements, m))
+}
+
+/// Estimate the number of distinct items that would set `ones` of the `m` bits of a filter
+/// with `k` hash functions: *`-(m/k)ln(1 - X/m)`*.
+#[cfg(feature = "std")]
+fn estimate_items(m: usize, k: usize, ones: usize) -> f64 {
+    let (m, k) = (m as f64, k as f64);
+    -(m / k) * (1.0 - ones as f64 / m).ln()
+}
+
+/// Calculate the size in bits, `m`, and number of hash functions, `k`, of a filter built
+/// from `n` and `f`.
+///
+/// `m` is computed from `f` as an `f64`, so that it agrees with the target rate the filter
+/// records, and rounded up to a whole number of bytes so that no storage goes unused. This
+/// is independent of the storage word, so filters set the same bits whatever it is. An
+/// [`AtomicBloomFilter`] is sized the same way, so that it sets the same bits as a
+/// [`BloomFilter`].
+///
+/// [`AtomicBloomFilter`]: atomic::AtomicBloomFilter
+#[cfg(feature = "std")]
+fn calc_sizing(n: usize, f: f64) -> (usize, usize) {
+    let m = optimal_params(n, f).0.next_multiple_of(8);
+    (m, calc_k(n, m))
+}
+
+/// Calculate the size in bits, `m`, number of hash functions, `k`, and false positive rate
+/// at `n` elements, `f`, of a filter for `n` elements taking `max_bytes` bytes.
+///
+/// *`m = 8 * max_bytes`*, `k` is optimal for it, and *`f = (1 - e^(-kn/m))^k`*. Returns
+/// `None` if `m` overflows `usize`.
+#[cfg(feature = "std")]
+fn calc_budget_sizing(n: usize, max_bytes: usize) -> Option<(usize, usize, f64)> {
+    let m = max_bytes.checked_mul(8)?;
+    let k = calc_k(n, m);
+    let f = (1.0 - (-(k as f64) * n as f64 / m as f64).exp()).powi(k as i32);
+    Some((m, k, f))
+}
+
+/// Calculate the appropriate size in bits of the Bloom filter, `m`, given
+/// `n` and `f`, the expected number of elements contained in the Bloom filter and the
+/// target false positive rate, respectively.
+///
+/// See [`optimal_params`].
+#[cfg(feature = "std")]
+fn calc_m(n: usize, f: f32) -> usize {
+    optimal_params(n, f as f64).0
+}
+
+/// Calculate the number of elements, `n`, for which a Bloom filter of `m` bits and `k` hash
+/// functions is optimally sized, and its false positive rate `f` at `n` elements.
+///
+/// *`n = mln(2)/k`*, the inverse of [`calc_k`], at which half the bits are set, so
+/// *`f = 2^-k`*.
+fn calc_design(m: usize, k: usize) -> (usize, f64) {
+    let n = (m as f64 * LN_2 / k.max(1) as f64) as usize;
+    // Halve repeatedly, as `powi` needs `std`
+    let f = (0..k).fold(1.0, |f, _| f * 0.5);
+    (n, f)
+}
+
+/// Calculate the number of hash functions to use, `k`, given `n` and `m`, the expected
+/// number of elements contained in the Bloom filter and the size in bits of the Bloom
+/// filter.
+///
+/// *`(mln(2)/n)`*, treating `n` as at least 1 and clamped between 1 and
+/// [`MAX_NUM_HASH_FUNCTIONS`].
+#[cfg(feature = "std")]
+fn calc_k(n: usize, m: usize) -> usize {
+    // https://en.wikipedia.org/wiki/Bloom_filter#Optimal_number_of_hash_functions
+    (((m as f64 * LN_2) / n.max(1) as f64) as usize).clamp(1, MAX_NUM_HASH_FUNCTIONS)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn check_sync<T: Sync>(_t: &T) {}
+
+    fn check_send<T: Send>(_t: &T) {}
+
+    #[test]
+    fn m() {
+        let (n, f) = (1_000_000, 0.02);
+        assert_eq!(calc_m(n, f), 8_142_363);
+    }
+
+    #[test]
+    fn k() {
+        let (n, m) = (1_000_000, 8_142_363);
+        assert_eq!(calc_k(n, m), 5);
+    }
+
+    #[test]
+    fn optimal_params() {
+        assert_eq!(crate::optimal_params(1_000_000, 0.02), (8_142_363, 5));
+        assert_eq!(
+            crate::optimal_params(1_000, 0.01),
+            (calc_m(1_000, 0.01), calc_k(1_000, calc_m(1_000, 0.01)))
+        );
+    }
+
+    #[test]
+    fn sizing() {
+        // Sized from the `f64` rate, where truncating it to `f32` would make `m` differ
+        for (n, f) in [(1_000_000, 1e-40), (100_000_000, 0.01), (1_000, 0.01)] {
+            let (m, k) = calc_sizing(n, f);
+            assert_eq!(m, crate::optimal_params(n, f).0.next_multiple_of(8));
+            assert_eq!(k, calc_k(n, m));
+        }
+        assert_eq!(calc_sizing(1_000_000, 1e-40).0, 191_701_168);
+        assert_eq!(calc_sizing(100_000_000, 0.01).0, 958_505_840);
+    }
+
+    #[test]
+    fn m_large_n() {
+        // `f32` rounding can err either way; for these parameters it undersizes the filter
+        let (n, f) = (50_000_000, 0.05);
+        let reference = (-f64::ln(f) * n as f64 / (2_f64.ln() * 2_f64.ln())) as usize;
+        let single_precision = (-f32::ln(f as f32) * n as f32 / 2_f32.ln().powf(2.0)) as usize;
+        let (m, _) = crate::optimal_params(n, f);
+        assert_eq!(m, reference);
+        assert!(m > single_precision, "m={} f32 m={}", m, single_precision);
+    }
+
+    #[test]
+    fn new_one_in() {
+        let bloom = BloomFilter::new_one_in(1_000, 100);
+        let reference = BloomFilter::new(1_000, 0.01);
+        assert_eq!(bloom.num_bits(), reference.num_bits());
+        assert_eq!(bloom.num_hash_functions(), reference.num_hash_functions());
+        assert_eq!(bloom.target_false_positive_rate(), 0.01);
+    }
+
+    #[test]
+    #[should_panic(expected = "one_in must be at least 2")]
+    fn new_one_in_one() {
+        BloomFilter::new_one_in(1_000, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "max_bytes must be at most usize::MAX / 8")]
+    fn with_memory_budget_overflow() {
+        BloomFilter::with_memory_budget(1_000, usize::MAX / 8 + 1);
+    }
+
+    #[test]
+    fn new_no_elements() {
+        let mut bloom = BloomFilter::new(0, 0.5);
+        assert!(bloom.num_bits() >= MIN_NUM_BITS);
+        assert!(bloom.num_hash_functions() >= 1);
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+    }
+
+    #[test]
+    fn new_high_false_positive_rate() {
+        let mut bloom = BloomFilter::new(1, 0.999);
+        assert!(bloom.num_bits() >= MIN_NUM_BITS);
+        assert!(bloom.num_hash_functions() >= 1);
+        assert!(bloom.size() > 0);
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+    }
+
+    #[test]
+    fn with_memory_budget() {
+        for (n, max_bytes) in [(0, 1), (1_000, 1_000), (1_000, 100), (1_000_000, 1_000)] {
+            let mut bloom = BloomFilter::with_memory_budget(n, max_bytes);
+            assert!(bloom.size() <= max_bytes);
+            assert_eq!(bloom.num_bits(), max_bytes * 8);
+            assert!(bloom.num_hash_functions() >= 1);
+            assert_eq!(bloom.capacity(), n);
+            bloom.insert(&"hi");
+            assert!(bloom.contains(&"hi"));
+        }
+
+        // About as many bytes as `new` picks give about its rate, fewer a worse one
+        let reference = BloomFilter::new(1_000, 0.01);
+        let bloom = BloomFilter::with_memory_budget(1_000, reference.size());
+        assert!((bloom.target_false_positive_rate() - 0.01).abs() < 0.001);
+        let smaller = BloomFilter::with_memory_budget(1_000, reference.size() / 2);
+        assert!(smaller.target_false_positive_rate() > 0.05);
+        assert!(BloomFilter::with_memory_budget(0, 1).target_false_positive_rate() == 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "max_bytes must be greater than zero")]
+    fn with_memory_budget_zero() {
+        BloomFilter::with_memory_budget(1_000, 0);
+    }
+
+    #[test]
+    fn new_normal() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        assert_eq!(bloom.num_bits(), calc_m(1_000, 0.01).next_multiple_of(8));
+        assert_eq!(bloom.num_hash_functions(), 6);
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+    }
+
+    #[test]
+    fn size() {
+        let bloom = BloomFilter::new(100_000, 0.01);
+        assert_eq!(bloom.size(), 119814);
+    }
+
+    #[test]
+    fn byte_aligned() {
+        // calc_m gives a non-multiple of 8 for these parameters
+        assert_ne!(calc_m(1_000_000, 0.02) % 8, 0);
+        let bloom = BloomFilter::new(1_000_000, 0.02);
+        assert_eq!(bloom.num_bits() % 8, 0);
+        assert_eq!(bloom.size() * 8, bloom.num_bits());
+    }
+
+    #[test]
+    fn params() {
+        let bloom = BloomFilter::new(1_000_000, 0.02);
+        assert_eq!(bloom.num_bits(), 8_142_368);
+        assert_eq!(bloom.num_hash_functions(), 5);
+    }
+
+    #[test]
+    fn with_params() {
+        let mut bloom = BloomFilter::with_params(1_000, 3).unwrap();
+        assert_eq!(bloom.num_bits(), 1_000);
+        assert_eq!(bloom.num_hash_functions(), 3);
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+    }
+
+    #[test]
+    fn with_params_and_hasher() {
+        let mut bloom = BloomFilter::with_params_and_hasher(
+            1_000,
+            3,
+            std::collections::hash_map::RandomState::new(),
+        )
+        .unwrap();
+        assert_eq!(bloom.num_bits(), 1_000);
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+        assert!(!bloom.contains(&"yo"));
+    }
+
+    #[test]
+    fn from_bit_count() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        let mut from_bit_count =
+            BloomFilter::from_bit_count(bloom.num_bits(), bloom.num_hash_functions());
+        assert_eq!(from_bit_count, bloom);
+        assert_eq!(from_bit_count.size(), bloom.size());
+
+        bloom.extend(0..100);
+        from_bit_count.extend(0..100);
+        assert_eq!(from_bit_count, bloom);
+    }
+
+    #[test]
+    #[should_panic(expected = "num_bits")]
+    fn from_bit_count_zero_bits() {
+        BloomFilter::from_bit_count(0, 3);
+    }
+
+    #[test]
+    fn with_params_zero_bits() {
+        assert!(matches!(
+            BloomFilter::with_params(0, 3),
+            Err(BloomError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn with_params_zero_hash_functions() {
+        assert!(matches!(
+            BloomFilter::with_params(1_000, 0),
+            Err(BloomError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn with_params_too_many_hash_functions() {
+        assert!(BloomFilter::with_params(1_000, MAX_NUM_HASH_FUNCTIONS).is_ok());
+        assert!(matches!(
+            BloomFilter::with_params(1_000, MAX_NUM_HASH_FUNCTIONS + 1),
+            Err(BloomError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn k_clamped() {
+        // Sized for a single item, the optimal `k` would be in the millions
+        let bloom = BloomFilter::with_memory_budget(1, 1 << 20);
+        assert_eq!(bloom.num_hash_functions(), MAX_NUM_HASH_FUNCTIONS);
+        assert!(BloomFilter::from_bytes(&bloom.to_bytes()).is_ok());
+    }
+
+    #[test]
+    fn with_hasher() {
+        // 64-bit FNV-1a, a fast deterministic non-cryptographic hasher
+        struct Fnv(u64);
+
+        impl Default for Fnv {
+            fn default() -> Fnv {
+                Fnv(0xcbf2_9ce4_8422_2325)
+            }
+        }
+
+        impl Hasher for Fnv {
+            fn finish(&self) -> u64 {
+                self.0
+            }
+
+            fn write(&mut self, bytes: &[u8]) {
+                for byte in bytes {
+                    self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100_0000_01b3);
+                }
+            }
+        }
+
+        let mut bloom = BloomFilter::with_hasher(1_000, 0.01, BuildHasherDefault::<Fnv>::default());
+        for i in 0..1_000 {
+            bloom.insert(&i);
+        }
+        for i in 0..1_000 {
+            assert!(bloom.contains(&i));
+        }
+        let false_positives = (1_000..11_000).filter(|i| bloom.contains(i)).count();
+        assert!(false_positives < 150, "false_positives={}", false_positives);
+
+        let restored = BloomFilter::from_bytes_with_hasher(
+            &bloom.to_bytes(),
+            BuildHasherDefault::<Fnv>::default(),
+        )
+        .unwrap();
+        assert_eq!(restored, bloom);
+        assert!((0..1_000).all(|i| restored.contains(&i)));
+    }
+
+    #[test]
+    fn identity_hasher() {
+        let mut bloom =
+            BloomFilter::with_params_and_hasher(64, 2, IdentityHashBuilder::default()).unwrap();
+        bloom.extend(0_u64..10);
+
+        // Item `x` sets bits `x` and `x + (x + 1 | 1)`
+        assert_eq!(
+            bloom.set_bits().collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 12, 13, 16, 17, 20]
+        );
+        assert_eq!(bloom.bit_indices(&9_u64), [9, 20]);
+        assert_eq!(bloom.bit_indices(&9_i32), [9, 20]);
+        assert!(!bloom.contains(&10_u64));
+
+        let mut hasher = IdentityHasher::default();
+        hasher.write(&[1, 2]);
+        hasher.write_i8(-1);
+        hasher.write_u128(1 << 64 | 3);
+        assert_eq!(hasher.finish(), 6);
+    }
+
+    #[test]
+    fn sip128_hasher() {
+        // Both hashes are the halves of a single 128-bit hash of the item
+        let mut hasher = Sip128Hasher::default();
+        "hi".hash(&mut hasher);
+        let hash = hasher.finish128();
+        assert_eq!(
+            hash_pair(&Sip128HashBuilder::default(), 0, "hi"),
+            (hash.h1, hash.h2)
+        );
+        assert_ne!(
+            hash_pair(&Sip128HashBuilder::default(), 7, "hi"),
+            (hash.h1, hash.h2)
+        );
+
+        let mut bloom = BloomFilter::with_hasher(10_000, 0.01, Sip128HashBuilder::default());
+        bloom.extend(0..10_000);
+        assert!((0..10_000).all(|i| bloom.contains(&i)));
+        let false_positives = (10_000..110_000).filter(|i| bloom.contains(i)).count();
+        let rate = false_positives as f64 / 100_000.0;
+        assert!(rate < 0.015, "rate={}", rate);
+
+        // Every sixteenth of the filter should receive roughly a sixteenth of the bits set by
+        // all of the derived hash functions
+        let mut bloom =
+            BloomFilter::with_params_and_hasher(1 << 24, 4, Sip128HashBuilder::default()).unwrap();
+        bloom.extend(0..50_000);
+        let mut buckets = [0; 16];
+        for b in bloom.bits.iter_ones() {
+            buckets[b / (1 << 20)] += 1;
+        }
+        // About 1_200 of the 200_000 bits set are expected to collide
+        assert!(buckets.iter().sum::<usize>() > 198_000);
+        for count in buckets {
+            assert!(
+                (count as f64 - 12_500.0).abs() < 625.0,
+                "buckets={:?}",
+                buckets
+            );
+        }
+    }
+
+    #[test]
+    fn estimate_count() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        for i in 0..100_000 {
+            bloom.insert(&i);
+        }
+        let estimate = bloom.estimate_count() as f64;
+        assert!((estimate - 100_000.0).abs() / 100_000.0 < 0.05);
+    }
+
+    #[test]
+    fn reset_with() {
+        let mut bloom = BloomFilter::new_with_seed(100_000, 0.01, 42);
+        bloom.extend(0..1_000);
+        let capacity = bloom.bits.capacity();
+        let ptr = bloom.bits.as_raw_slice().as_ptr();
+
+        bloom.reset_with(1_000, 0.05);
+        assert_eq!(bloom.bits.capacity(), capacity);
+        assert_eq!(bloom.bits.as_raw_slice().as_ptr(), ptr);
+        assert!(bloom.is_empty());
+        assert!((0..1_000).all(|i| !bloom.contains(&i)));
+        assert_eq!(bloom.seed(), 42);
+        assert_eq!(bloom.expected_elements(), 1_000);
+        assert_eq!(bloom, BloomFilter::new_with_seed(1_000, 0.05, 42));
+
+        bloom.reset_with(1_000_000, 0.01);
+        assert_eq!(bloom, BloomFilter::new_with_seed(1_000_000, 0.01, 42));
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+    }
+
+    #[test]
+    fn design() {
+        let bloom = BloomFilter::new(1_000, 0.01);
+        assert_eq!(bloom.expected_elements(), 1_000);
+        assert_eq!(bloom.target_false_positive_rate() as f32, 0.01);
+
+        let bloom = BloomFilter::builder()
+            .expected_items(5_000)
+            .false_positive_rate(0.001)
+            .build()
+            .unwrap();
+        assert_eq!(bloom.expected_elements(), 5_000);
+        assert_eq!(bloom.target_false_positive_rate(), 0.001);
+
+        let bloom = BloomFilter::with_params(9_586, 7).unwrap();
+        assert_eq!(bloom.expected_elements(), 949);
+        assert_eq!(bloom.target_false_positive_rate(), 1.0 / 128.0);
+    }
+
+    #[test]
+    fn union_different_design() {
+        // Same geometry, built for different purposes
+        let mut a = BloomFilter::new(1_000, 0.01);
+        let b = BloomFilter::with_params(a.num_bits(), a.num_hash_functions()).unwrap();
+        assert_eq!(a.union(&b), Err(BloomError::MismatchedParameters));
+    }
+
+    #[test]
+    fn saturated() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        assert_eq!(bloom.capacity(), 1_000);
+        for i in 0..1_000 {
+            bloom.insert(&i);
+            bloom.insert(&i);
+        }
+        assert!(!bloom.is_saturated());
+        for i in 1_000..1_100 {
+            bloom.insert(&i);
+        }
+        assert!(bloom.is_saturated());
+
+        // A deserialized filter is still saturated, going by the items it's estimated to hold
+        let restored = BloomFilter::from_bytes(&bloom.to_bytes()).unwrap();
+        assert!(restored.is_saturated());
+        let mut half = BloomFilter::new(1_000, 0.01);
+        half.extend(0..500);
+        assert!(!BloomFilter::from_bytes(&half.to_bytes())
+            .unwrap()
+            .is_saturated());
+
+        bloom.clear();
+        assert!(!bloom.is_saturated());
+
+        let bloom = BloomFilter::with_params(9_586, 7).unwrap();
+        assert_eq!(bloom.capacity(), 949);
+    }
+
+    #[test]
+    fn is_empty() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        assert!(bloom.is_empty());
+        assert_eq!(bloom.len(), 0);
+
+        bloom.insert(&"hi");
+        assert!(!bloom.is_empty());
+        assert_eq!(bloom.len(), 1);
+
+        bloom.clear();
+        assert!(bloom.is_empty());
+    }
+
+    #[test]
+    fn estimate_jaccard() {
+        let mut a = BloomFilter::new(100_000, 0.01);
+        let mut b = BloomFilter::new(100_000, 0.01);
+        a.extend(0..50_000);
+        b.extend(10_000..60_000);
+        // 40,000 shared items out of 60,000 distinct ones
+        let jaccard = a.estimate_jaccard(&b).unwrap();
+        assert!((jaccard - 2.0 / 3.0).abs() < 0.02, "jaccard={}", jaccard);
+        assert!((a.estimate_jaccard(&a).unwrap() - 1.0).abs() < 1e-9);
+
+        let empty = BloomFilter::new(100_000, 0.01);
+        assert_eq!(empty.estimate_jaccard(&empty).unwrap(), 1.0);
+        assert!(a.estimate_jaccard(&empty).unwrap() < 0.01);
+
+        assert!(matches!(
+            a.estimate_jaccard(&BloomFilter::new(1_000, 0.01)),
+            Err(BloomError::MismatchedParameters)
+        ));
+    }
+
+    #[test]
+    fn padding_ignored() {
+        // Padding bits past `m` in the last byte are neither counted nor merged
+        let clean = BloomFilter::with_params(1001, 3).unwrap();
+        let mut dirty = clean.clone();
+        *dirty.bits.as_raw_mut_slice().last_mut().unwrap() |= 0b1111_1110;
+        assert_eq!(dirty, clean);
+        assert!(dirty.is_empty());
+        assert_eq!(dirty.estimate_jaccard(&clean).unwrap(), 1.0);
+        assert_eq!(dirty.estimate_union_count(&clean).unwrap(), 0);
+        assert_eq!(dirty.estimate_intersection_count(&dirty).unwrap(), 0);
+
+        let mut union = clean.clone();
+        union.union(&dirty).unwrap();
+        assert_eq!(union.as_raw_slice(), clean.as_raw_slice());
+        let mut intersection = dirty.clone();
+        intersection.intersect(&dirty).unwrap();
+        assert_eq!(intersection.as_raw_slice(), clean.as_raw_slice());
+    }
+
+    #[test]
+    fn estimate_union_intersection_count() {
+        let mut a = BloomFilter::new(100_000, 0.01);
+        let mut b = BloomFilter::new(100_000, 0.01);
+        a.extend(0..50_000);
+        b.extend(10_000..60_000);
+
+        let union = a.estimate_union_count(&b).unwrap();
+        assert!((59_000..=61_000).contains(&union), "union={}", union);
+        let mut merged = a.clone();
+        merged.union(&b).unwrap();
+        assert_eq!(union, merged.estimate_count());
+
+        let intersection = a.estimate_intersection_count(&b).unwrap();
+        assert!(
+            (39_000..=41_000).contains(&intersection),
+            "intersection={}",
+            intersection
+        );
+
+        let empty = BloomFilter::new(100_000, 0.01);
+        assert_eq!(a.estimate_union_count(&empty).unwrap(), a.estimate_count());
+        assert_eq!(a.estimate_intersection_count(&empty).unwrap(), 0);
+
+        let other = BloomFilter::new(1_000, 0.01);
+        assert_eq!(
+            a.estimate_union_count(&other),
+            Err(BloomError::MismatchedParameters)
+        );
+        assert_eq!(
+            a.estimate_intersection_count(&other),
+            Err(BloomError::MismatchedParameters)
+        );
+    }
+
+    #[test]
+    fn estimate_added_count() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        bloom.extend(0..20_000);
+        let snapshot = bloom.clone();
+        assert_eq!(bloom.estimate_added_count(&snapshot), Ok(0));
+
+        bloom.extend(10_000..40_000);
+        let added = bloom.estimate_added_count(&snapshot).unwrap();
+        assert!((19_000..=21_000).contains(&added), "added={}", added);
+        assert_eq!(snapshot.estimate_added_count(&bloom), Ok(0));
+
+        assert_eq!(
+            bloom.estimate_added_count(&BloomFilter::new(1_000, 0.01)),
+            Err(BloomError::MismatchedParameters)
+        );
+    }
+
+    #[test]
+    fn insert_lines() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        let input = "apple\n  banana \r\n\ncherry";
+        assert_eq!(bloom.insert_lines(std::io::Cursor::new(input)).unwrap(), 3);
+        for line in ["apple", "banana", "cherry"] {
+            assert!(bloom.contains(line));
+        }
+        assert!(!bloom.contains(""));
+
+        let err = bloom.insert_lines(&b"durian\n\xff\n"[..]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(bloom.contains("durian"));
+    }
+
+    #[test]
+    fn hamming_distance() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        bloom.extend(0..100);
+        assert_eq!(bloom.hamming_distance(&bloom), Ok(0));
+
+        // A superset differs by exactly the bits only it has set
+        let mut superset = bloom.clone();
+        superset.extend(100..300);
+        let extra = superset.set_bits().count() - bloom.set_bits().count();
+        assert!(extra > 0);
+        assert_eq!(bloom.hamming_distance(&superset), Ok(extra));
+        assert_eq!(superset.hamming_distance(&bloom), Ok(extra));
+
+        assert_eq!(
+            bloom.hamming_distance(&BloomFilter::new(100, 0.01)),
+            Err(BloomError::MismatchedParameters)
+        );
+    }
+
+    #[test]
+    fn current_false_positive_rate() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        assert_eq!(bloom.current_false_positive_rate(), 0.0);
+        for i in 0..100_000 {
+            bloom.insert(&i);
+        }
+        let rate = bloom.current_false_positive_rate();
+        assert!((rate - 0.01).abs() < 0.002, "rate={}", rate);
+    }
+
+    #[test]
+    fn measure_false_positive_rate() {
+        let mut bloom = BloomFilter::new(10_000, 0.01);
+        bloom.extend(0..10_000_u64);
+
+        let mut next = 1 << 32;
+        let rate = bloom.measure_false_positive_rate(100_000, || {
+            next += 1;
+            next
+        });
+        assert!((rate - 0.01).abs() < 0.002, "rate={}", rate);
+        assert!((rate - bloom.current_false_positive_rate()).abs() < 0.002);
+
+        // Inserted items are all counted, as they're never false negatives
+        let mut next = 0;
+        let rate = bloom.measure_false_positive_rate(1_000, || {
+            next += 1;
+            next
+        });
+        assert_eq!(rate, 1.0);
+        assert_eq!(bloom.measure_false_positive_rate(0, || 0), 0.0);
+    }
+
+    #[test]
+    fn target_fill_ratio() {
+        for (n, f) in [(1_000, 0.01), (100_000, 0.001), (10_000, 0.1)] {
+            let bloom = BloomFilter::new(n, f);
+            let fill = bloom.target_fill_ratio();
+            assert!((fill - 0.5).abs() < 0.05, "n={} f={} fill={}", n, f, fill);
+        }
+
+        // The filter reaches its target false positive rate at that fill
+        let mut bloom = BloomFilter::new(10_000, 0.01);
+        let mut i = 0;
+        while bloom.fill_ratio() < bloom.target_fill_ratio() {
+            bloom.insert(&i);
+            i += 1;
+        }
+        let rate = bloom.current_false_positive_rate();
+        assert!((rate - 0.01).abs() < 0.0005, "rate={}", rate);
+        assert!((9_500..=10_500).contains(&i), "i={}", i);
+    }
+
+    #[test]
+    fn remaining_capacity() {
+        let mut bloom = BloomFilter::new(10_000, 0.01);
+        let empty = bloom.remaining_capacity(0.01);
+        assert!((9_500..=10_500).contains(&empty), "empty={}", empty);
+        assert!(bloom.remaining_capacity(0.001) < empty);
+
+        let mut last = empty;
+        for chunk in 0..4 {
+            bloom.extend(chunk * 2_000..(chunk + 1) * 2_000);
+            let remaining = bloom.remaining_capacity(0.01);
+            assert!(remaining < last, "remaining={} last={}", remaining, last);
+            assert!(
+                (remaining as i64 - (empty as i64 - 2_000 * (chunk + 1))).abs() < 300,
+                "remaining={}",
+                remaining
+            );
+            last = remaining;
+        }
+
+        // Past the target there's nothing left
+        bloom.extend(8_000..12_000);
+        assert_eq!(bloom.remaining_capacity(0.01), 0);
+        assert_eq!(bloom.remaining_capacity(0.0), 0);
+        assert_eq!(bloom.remaining_capacity(f64::NAN), 0);
+    }
+
+    #[test]
+    fn fill_ratio() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        assert_eq!(bloom.fill_ratio(), 0.0);
+        for i in 0..10 {
+            bloom.insert(&i);
+        }
+        let ratio = bloom.fill_ratio();
+        assert!(ratio > 0.0 && ratio < 0.001, "ratio={}", ratio);
+    }
+
+    #[test]
+    fn insert_check() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        assert!(bloom.insert_check(&"hi"));
+        assert!(!bloom.insert_check(&"hi"));
+        assert!(bloom.insert_check(&"yo"));
+    }
+
+    #[test]
+    fn insert_and_report() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        let k = bloom.num_hash_functions();
+        assert_eq!(bloom.insert_and_report(&"hi"), k);
+        assert_eq!(bloom.insert_and_report(&"hi"), 0);
+
+        let flipped: usize = (0..1_000).map(|i| bloom.insert_and_report(&i)).sum();
+        assert_eq!(flipped + k, bloom.bits.count_ones());
+    }
+
+    #[test]
+    fn contains_or_insert() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        assert!(!bloom.contains_or_insert(&"hi"));
+        assert!(bloom.contains_or_insert(&"hi"));
+        assert!(bloom.contains(&"hi"));
+    }
+
+    #[test]
+    fn get_or_insert_with() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        let mut first_seen = Vec::new();
+        for item in ["a", "b", "a", "c", "b", "a"] {
+            bloom.get_or_insert_with(&item, || first_seen.push(item));
+        }
+        assert_eq!(first_seen, ["a", "b", "c"]);
+        assert!(bloom.contains_all(&["a", "b", "c"]));
+    }
+
+    #[test]
+    fn default() {
+        let mut bloom = BloomFilter::default();
+        assert!(bloom.num_bits() > 0);
+        assert_eq!(bloom, BloomFilter::new(1_000, 0.01));
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+        assert!(!bloom.contains(&"yo"));
+    }
+
+    #[test]
+    fn from_iter() {
+        let items = vec!["a", "b", "c"];
+        let bloom: BloomFilter = items.iter().collect();
+        for item in &items {
+            assert!(bloom.contains(item));
+        }
+    }
+
+    #[test]
+    fn rebuild_from() {
+        let items: Vec<u32> = (0..10_000).collect();
+        let mut loose = BloomFilter::new(10_000, 0.1);
+        loose.extend(&items);
+
+        let bloom = BloomFilter::rebuild_from(&items, items.len(), 0.001);
+        assert_eq!(bloom.expected_elements(), 10_000);
+        assert_eq!(bloom.target_false_positive_rate(), 0.001);
+        assert_eq!(
+            bloom.num_bits(),
+            BloomFilter::builder()
+                .expected_items(10_000)
+                .false_positive_rate(0.001)
+                .build()
+                .unwrap()
+                .num_bits()
+        );
+        assert!(bloom.contains_all(&items));
+        let false_positives = (10_000..110_000u32).filter(|i| bloom.contains(i)).count();
+        assert!(false_positives < 200, "false_positives={}", false_positives);
+        assert!(loose.current_false_positive_rate() > bloom.current_false_positive_rate());
+    }
+
+    #[test]
+    fn extend() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        bloom.insert(&"hi");
+        bloom.extend(vec!["a", "b", "c"]);
+        for item in ["hi", "a", "b", "c"] {
+            assert!(bloom.contains(&item));
+        }
+    }
+
+    #[test]
+    fn no_false_negatives() {
+        for (n, f) in [(100, 0.1), (10_000, 0.01), (10_000, 0.0001)] {
+            let mut bloom = BloomFilter::new(n, f);
+            for i in 0..n {
+                bloom.insert(&i);
+            }
+            for i in 0..n {
+                assert!(bloom.contains(&i));
+            }
+        }
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let mut bloom = BloomFilter::new(10_000, 0.01);
+        for i in 0..10_000 {
+            bloom.insert(&i);
+        }
+        let false_positives = (10_000..110_000).filter(|i| bloom.contains(i)).count();
+        let rate = false_positives as f64 / 100_000.0;
+        assert!(rate < 0.015, "rate={}", rate);
+    }
+
+    #[test]
+    fn calc_index_uses_full_hash() {
+        // Truncating the hash to 32 bits before the modulus would give 2^32 % 3 = 1
+        assert_eq!(calc_index(1 << 40, 0, 0, 3), ((1_u64 << 40) % 3) as usize);
+        assert_eq!(
+            calc_index(u64::MAX, 0, 0, 1_000),
+            (u64::MAX % 1_000) as usize
+        );
+    }
+
+    #[test]
+    fn bits_spread_over_range() {
+        let mut bloom = BloomFilter::with_params(1 << 24, 1).unwrap();
+        for i in 0..100_000 {
+            bloom.insert(&i);
+        }
+
+        // Every sixteenth of the filter should receive roughly a sixteenth of the bits
+        let mut buckets = [0; 16];
+        for b in bloom.bits.iter_ones() {
+            buckets[b / (1 << 20)] += 1;
+        }
+        for count in buckets {
+            assert!(
+                (count as f64 - 6_250.0).abs() < 625.0,
+                "buckets={:?}",
+                buckets
+            );
+        }
+    }
+
+    #[test]
+    fn contains_true() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+    }
+
+    #[test]
+    fn contains_borrowed() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        bloom.insert(&String::from("hi"));
+        assert!(bloom.contains("hi"));
+        assert!(bloom.contains(&"hi"));
+        assert!(bloom.contains(&String::from("hi")));
+
+        bloom.insert("yo");
+        assert!(bloom.contains(&String::from("yo")));
+
+        bloom.insert(&vec![1_u8, 2, 3]);
+        assert!(bloom.contains(&[1_u8, 2, 3][..]));
+        assert!(!bloom.contains("sup"));
+    }
+
+    #[test]
+    fn contains_false() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        bloom.insert(&"hi");
+        assert!(!bloom.contains(&"yo"));
+    }
+
+    #[test]
+    fn hashes_match_items() {
+        fn hashes<T: Hash>(item: &T) -> (u64, u64) {
+            let hash_builder = DefaultHashBuilder::default();
+            let mut hasher = hash_builder.build_hasher();
+            hasher.write_u8(1);
+            item.hash(&mut hasher);
+            (hash_builder.hash_one(item), hasher.finish())
+        }
+
+        let mut by_item = BloomFilter::new(1_000, 0.01);
+        let mut by_hashes = BloomFilter::new(1_000, 0.01);
+        for i in 0..100 {
+            by_item.insert(&i);
+            let (h1, h2) = hashes(&i);
+            by_hashes.insert_hashes(h1, h2);
+        }
+        assert_eq!(by_item, by_hashes);
+        for i in 0..100 {
+            let (h1, h2) = hashes(&i);
+            assert!(by_item.contains_hashes(h1, h2));
+            assert!(by_hashes.contains(&i));
+        }
+    }
+
+    #[test]
+    fn insert_bytes() {
+        let mut bloom = BloomFilter::new(10_000, 0.01);
+        for i in 0..1_000_u32 {
+            bloom.insert_bytes(&i.to_le_bytes());
+        }
+        for i in 0..1_000_u32 {
+            assert!(bloom.contains_bytes(&i.to_le_bytes()));
+        }
+        assert!(!bloom.contains_bytes(b"hi"));
+
+        let mut seeded = BloomFilter::new_with_seed(10_000, 0.01, 42);
+        seeded.insert_bytes(b"hi");
+        assert!(seeded.contains_bytes(b"hi"));
+        assert!(!seeded.contains(&"hi"));
+    }
+
+    #[test]
+    fn set_bits() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        assert_eq!(bloom.set_bits().count(), 0);
+
+        bloom.insert(&"hi");
+        let set: Vec<usize> = bloom.set_bits().collect();
+        assert!(!set.is_empty() && set.len() <= bloom.num_hash_functions());
+        assert!(set.windows(2).all(|w| w[0] < w[1]));
+        for i in set {
+            assert!(bloom.bits[i]);
+        }
+    }
+
+    #[test]
+    fn count_ones() {
+        fn check<W: BitRegister + BitStore>() {
+            for m in [1, 7, 8, 63, 64, 65, 1_000, 4_099] {
+                let mut bloom = BloomFilter::builder()
+                    .expected_items(1_000)
+                    .storage::<W>()
+                    .build()
+                    .unwrap();
+                bloom.bits = BitVec::repeat(false, m);
+                bloom.extend(0..m / 4);
+                let reference = bloom.bits.iter().filter(|bit| **bit).count();
+                assert_eq!(bloom.count_ones(), reference, "m={}", m);
+
+                // Set bits past `m` in the last word aren't counted
+                bloom.bits.set_uninitialized(true);
+                assert_eq!(bloom.count_ones(), reference, "m={}", m);
+                bloom.bits.fill(true);
+                assert_eq!(bloom.count_ones(), m, "m={}", m);
+            }
+        }
+
+        check::<u8>();
+        check::<u16>();
+        check::<u32>();
+        check::<u64>();
+    }
+
+    #[test]
+    fn storage_width_independent() {
+        fn filled<W: BitRegister + BitStore>() -> BloomFilter<DefaultHashBuilder, W> {
+            let mut bloom = BloomFilter::builder()
+                .expected_items(1_000)
+                .seed(7)
+                .storage::<W>()
+                .build()
+                .unwrap();
+            bloom.extend(0..500);
+            bloom
+        }
+
+        fn check_same<W: BitRegister + BitStore>() {
+            let narrow = filled::<u8>();
+            let bloom = filled::<W>();
+            assert_eq!(bloom.size(), narrow.size());
+            assert_eq!(bloom.num_bits(), narrow.num_bits());
+            assert!(bloom.set_bits().eq(narrow.set_bits()));
+            assert_eq!(bloom.estimate_count(), narrow.estimate_count());
+            assert_eq!(bloom.to_bytes(), narrow.to_bytes());
+            assert!((200..800).all(|i| bloom.contains(&i) == narrow.contains(&i)));
+        }
+
+        check_same::<u16>();
+        check_same::<u32>();
+        check_same::<u64>();
+
+        let mut wide = filled::<u64>();
+        let mut other = BloomFilter::builder()
+            .expected_items(1_000)
+            .seed(7)
+            .storage::<u64>()
+            .build()
+            .unwrap();
+        other.extend(500..1_000);
+        wide.union(&other).unwrap();
+        assert!((0..1_000).all(|i| wide.contains(&i)));
+        wide.clear();
+        assert_eq!(wide.set_bits().count(), 0);
+    }
+
+    #[test]
+    fn index_collisions() {
+        let collisions = |enhanced: bool, items: &[u64]| -> usize {
+            let mut bloom =
+                BloomFilter::with_params_and_hasher(1_000, 20, IdentityHashBuilder::default())
+                    .unwrap();
+            bloom.enhanced_double_hashing = enhanced;
+            items.iter().map(|item| bloom.index_collisions(item)).sum()
+        };
+
+        // With `h2` an odd multiple of 125, plain double hashing only reaches 8 of the 1000
+        // bits, so 12 of an item's 20 indices collide, while enhanced double hashing spreads
+        // them out
+        let degenerate: Vec<u64> = (0..100).map(|j| 125 * (2 * j + 1) - 1).collect();
+        assert_eq!(collisions(false, &degenerate), 12 * degenerate.len());
+        assert!(collisions(true, &degenerate) < degenerate.len());
+
+        // For typical items both schemes rarely collide, plain double hashing even less as its
+        // `h2` is odd, so it only collapses when the size has a large odd factor
+        let plain = BloomFilter::new(1_000, 0.01);
+        let mut enhanced = plain.clone();
+        enhanced.enhanced_double_hashing = true;
+        let plain: usize = (0..10_000).map(|i| plain.index_collisions(&i)).sum();
+        let enhanced: usize = (0..10_000).map(|i| enhanced.index_collisions(&i)).sum();
+        assert!(plain <= enhanced, "plain={} enhanced={}", plain, enhanced);
+        assert!(enhanced < 100, "enhanced={}", enhanced);
+    }
+
+    #[test]
+    fn enhanced_double_hashing() {
+        let mut plain = BloomFilter::new(1_000, 0.01);
+        let mut enhanced = plain.clone();
+        enhanced.enhanced_double_hashing = true;
+        assert!(!plain.enhanced_double_hashing());
+        assert!(enhanced.enhanced_double_hashing());
+        plain.extend(0..500);
+        enhanced.extend(0..500);
+        assert!((0..500).all(|i| enhanced.contains(&i)));
+        assert_ne!(plain.bit_indices(&1), enhanced.bit_indices(&1));
+        assert_ne!(plain, enhanced);
+        assert_eq!(
+            plain.clone().union(&enhanced),
+            Err(BloomError::MismatchedParameters)
+        );
+
+        // The scheme is recorded, so deserialized filters find the same items
+        let bytes = enhanced.to_bytes();
+        assert_eq!(
+            bytes[HEADER_LEN - 1],
+            HASH_SCHEME_VERSION | ENHANCED_DOUBLE_HASHING
+        );
+        let restored = BloomFilter::from_bytes(&bytes).unwrap();
+        assert!(restored.enhanced_double_hashing());
+        assert_eq!(restored, enhanced);
+        assert!(!BloomFilter::from_bytes(&plain.to_bytes())
+            .unwrap()
+            .enhanced_double_hashing());
+    }
+
+    #[test]
+    fn bit_indices() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        for item in ["hi", "yo", "sup"] {
+            let indices = bloom.bit_indices(item);
+            assert!(!indices.is_empty() && indices.len() <= bloom.num_hash_functions());
+            assert!(indices.iter().all(|&i| i < bloom.num_bits()));
+            assert!(indices.windows(2).all(|w| w[0] < w[1]));
+        }
+
+        bloom.insert("hi");
+        assert!(bloom.set_bits().eq(bloom.bit_indices("hi")));
+    }
+
+    #[test]
+    fn explain_contains() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        let explanation = bloom.explain_contains("hi");
+        assert_eq!(explanation.len(), bloom.num_hash_functions());
+        assert!(explanation.iter().all(|&(_, set)| !set));
+
+        // Fill the filter past capacity until an item that was never inserted shows up
+        let inserted: Vec<u32> = (0..2_000).collect();
+        bloom.insert_all(&inserted);
+        let absent = (2_000u32..).find(|item| bloom.contains(item)).unwrap();
+
+        let explanation = bloom.explain_contains(&absent);
+        assert!(explanation.iter().all(|&(_, set)| set));
+        let mut bits: Vec<usize> = explanation.iter().map(|&(bit, _)| bit).collect();
+        bits.sort_unstable();
+        bits.dedup();
+        assert_eq!(bits, bloom.bit_indices(&absent));
+        // Each of its bits was set by some other item
+        for bit in bits {
+            assert!(inserted
+                .iter()
+                .any(|item| bloom.bit_indices(item).contains(&bit)));
+        }
+    }
+
+    #[test]
+    fn insert_all() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        bloom.insert_all(&["a", "b", "c"]);
+        for item in ["a", "b", "c"] {
+            assert!(bloom.contains(&item));
+        }
+    }
+
+    #[test]
+    fn contains_all() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        bloom.insert_all(&["a", "b", "c"]);
+        assert!(bloom.contains_all(&["a", "b", "c"]));
+        assert!(bloom.contains_all::<&str>(&[]));
+        assert!(!bloom.contains_all(&["a", "b", "yo"]));
+    }
+
+    #[test]
+    fn count_contained() {
+        let candidates: Vec<u32> = (0..10_000).collect();
+        let mut bloom = BloomFilter::new(10_000, 0.01);
+        bloom.insert_all(&candidates[..5_000]);
+
+        let count = bloom.count_contained(&candidates);
+        assert!(count >= 5_000);
+        // Only the absent half can be false positives
+        assert!(count < 5_000 + 5_000 / 20, "count={}", count);
+        assert_eq!(bloom.count_contained::<u32>(&[]), 0);
+    }
+
+    #[test]
+    fn contains_any() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        bloom.insert(&"b");
+        assert!(bloom.contains_any(&["a", "b", "c"]));
+        assert!(!bloom.contains_any(&["x", "y", "z"]));
+        assert!(!bloom.contains_any::<&str>(&[]));
+    }
+
+    #[test]
+    fn contains_each() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        bloom.insert_all(&["a", "c"]);
+        assert_eq!(
+            bloom.contains_each(&["a", "b", "c", "d"]),
+            [true, false, true, false]
+        );
+        assert!(bloom.contains_each::<&str, _>(&[]).is_empty());
+
+        // Present items are always found, whatever the false positives among the others
+        let candidates: Vec<u32> = (0..10_000).collect();
+        bloom.extend(candidates.iter().filter(|i| *i % 3 == 0));
+        let found = bloom.contains_each(&candidates);
+        assert_eq!(found.len(), candidates.len());
+        assert!(candidates
+            .iter()
+            .zip(&found)
+            .all(|(i, &found)| found || i % 3 != 0));
+        assert!(found.iter().filter(|&&found| found).count() < 3_334 + 100);
+    }
+
+    #[test]
+    fn clear() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        bloom.insert(&"hi");
+        bloom.insert(&"yo");
+
+        bloom.clear();
+        assert!(!bloom.contains(&"hi"));
+        assert!(!bloom.contains(&"yo"));
+        assert_eq!(bloom.size(), 119814);
+    }
+
+    #[test]
+    fn clone_is_independent() {
+        let mut bloom = BloomFilter::new(100_000, 0.01);
+        bloom.insert(&"hi");
+
+        let mut snapshot = bloom.clone();
+        snapshot.insert(&"yo");
+        assert!(snapshot.contains(&"hi"));
+        assert!(snapshot.contains(&"yo"));
+        assert!(!bloom.contains(&"yo"));
+    }
+
+    #[test]
+    fn debug() {
+        let mut bloom = BloomFilter::with_params(64, 3).unwrap();
+        bloom.insert(&"hi");
+        let debug = format!("{:?}", bloom);
+        assert!(debug.starts_with("BloomFilter {"));
+        assert!(debug.contains("num_hash_functions: 3"));
+        assert!(debug.contains("num_bits: 64"));
+        assert!(debug.contains("size: 8"));
+        assert!(debug.contains(&format!("fill_ratio: {:?}", bloom.fill_ratio())));
+        assert!(!debug.contains('['));
+    }
+
+    #[test]
+    fn split() {
+        let mut bloom = BloomFilter::new_with_seed(1_000, 0.01, 42);
+        let items: Vec<u32> = (0..1_000).collect();
+        bloom.insert_all(&items);
+
+        for num_shards in [1, 3, 8] {
+            let shards = bloom.split(num_shards);
+            assert_eq!(shards.len(), num_shards);
+            let shard_len = bloom.num_bits().div_ceil(num_shards);
+            assert!(shards.iter().all(|shard| shard.seed() == 42));
+            assert_eq!(
+                shards.iter().map(|shard| shard.num_bits()).sum::<usize>(),
+                bloom.num_bits()
+            );
+
+            // Reassembling the shards reproduces the original bits
+            let reassembled = shards
+                .iter()
+                .enumerate()
+                .flat_map(|(i, shard)| shard.set_bits().map(move |b| i * shard_len + b));
+            assert!(reassembled.eq(bloom.set_bits()));
+
+            for item in &items {
+                assert!(bloom
+                    .bit_indices(item)
+                    .into_iter()
+                    .all(|b| shards[b / shard_len].is_bit_set(b % shard_len)));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "leave a shard with no bits")]
+    fn split_too_many_shards() {
+        BloomFilter::with_params(10, 3).unwrap().split(6);
+    }
+
+    #[test]
+    fn display() {
+        let mut bloom = BloomFilter::with_params(64, 3).unwrap();
+        assert_eq!(
+            bloom.to_string(),
+            "BloomFilter(m=64 bits, k=3, fill=0.0%, ~0 items)"
+        );
+
+        bloom.insert(&"hi");
+        let display = bloom.to_string();
+        assert!(display.contains("m=64 bits"));
+        assert!(display.contains("k=3"));
+        assert!(display.contains(&format!("fill={:.1}%", bloom.fill_ratio() * 100.0)));
+        assert!(display.ends_with(", ~1 items)"));
+    }
+
+    #[test]
+    fn same_seed_eq() {
+        let mut a = BloomFilter::new_with_seed(1_000, 0.01, 42);
+        let mut b = BloomFilter::new_with_seed(1_000, 0.01, 42);
+        for i in 0..100 {
+            a.insert(&i);
+            b.insert(&i);
+        }
+        assert_eq!(a.seed(), 42);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn different_seed_bits() {
+        let mut a = BloomFilter::new_with_seed(1_000, 0.01, 1);
+        let mut b = BloomFilter::new_with_seed(1_000, 0.01, 2);
+        a.insert(&"hi");
+        b.insert(&"hi");
+        assert_ne!(a.bits, b.bits);
+        assert_eq!(a.union(&b), Err(BloomError::MismatchedParameters));
+    }
+
+    #[test]
+    fn zero_seed_unseeded() {
+        let mut a = BloomFilter::new_with_seed(1_000, 0.01, 0);
+        let mut b = BloomFilter::new(1_000, 0.01);
+        a.insert(&"hi");
+        b.insert(&"hi");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn eq_order_independent() {
+        let mut a = BloomFilter::new(1_000, 0.01);
+        let mut b = BloomFilter::new(1_000, 0.01);
+        a.insert(&"hi");
+        a.insert(&"yo");
+        b.insert(&"yo");
+        b.insert(&"hi");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn ne_extra_item() {
+        let mut a = BloomFilter::new(1_000, 0.01);
+        let mut b = BloomFilter::new(1_000, 0.01);
+        a.insert(&"hi");
+        b.insert(&"hi");
+        b.insert(&"yo");
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn ne_num_hash_functions() {
+        let a = BloomFilter::with_params(1_000, 3).unwrap();
+        let b = BloomFilter::with_params(1_000, 4).unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn union() {
+        let mut a = BloomFilter::new(1_000, 0.01);
+        let mut b = BloomFilter::new(1_000, 0.01);
+        for i in 0..500 {
+            a.insert(&i);
+        }
+        for i in 500..1_000 {
+            b.insert(&i);
+        }
+
+        a.union(&b).unwrap();
+        for i in 0..1_000 {
+            assert!(a.contains(&i));
+        }
+    }
+
+    #[test]
+    fn union_all() {
+        let filters: Vec<_> = (0..3)
+            .map(|shard| {
+                let mut bloom = BloomFilter::new(3_000, 0.01);
+                bloom.extend(shard * 1_000..(shard + 1) * 1_000);
+                bloom
+            })
+            .collect();
+
+        let union = BloomFilter::union_all(&filters).unwrap();
+        assert!((0..3_000).all(|i| union.contains(&i)));
+        let mut expected = BloomFilter::new(3_000, 0.01);
+        expected.extend(0..3_000);
+        assert_eq!(union, expected);
+        assert_eq!(BloomFilter::union_all(&filters[..1]).unwrap(), filters[0]);
+
+        assert!(matches!(
+            BloomFilter::<DefaultHashBuilder>::union_all(&[]),
+            Err(BloomError::InvalidParameter(_))
+        ));
+        let mismatched = [filters[0].clone(), BloomFilter::new(1_000, 0.01)];
+        assert_eq!(
+            BloomFilter::union_all(&mismatched),
+            Err(BloomError::MismatchedParameters)
+        );
+    }
+
+    #[test]
+    fn downsample() {
+        let mut bloom = BloomFilter::with_params(1 << 20, 7).unwrap();
+        bloom.extend(0..1_000);
+
+        for factor in [1, 2, 8, 128] {
+            let small = bloom.downsample(factor).unwrap();
+            assert_eq!(small.num_bits() * factor, bloom.num_bits());
+            assert_eq!(small.num_hash_functions(), bloom.num_hash_functions());
+            assert!((0..1_000).all(|i| small.contains(&i)));
+        }
+
+        let small = bloom.downsample(128).unwrap();
+        let false_positives = (1_000..11_000).filter(|i| small.contains(i)).count();
+        let original = (1_000..11_000).filter(|i| bloom.contains(i)).count();
+        assert!(false_positives > original);
+
+        for factor in [0, 3, 6, bloom.num_bits() * 2] {
+            assert!(matches!(
+                bloom.downsample(factor),
+                Err(BloomError::InvalidParameter(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn as_raw_slice() {
+        for m in [8, 1_003, 4_096] {
+            let mut bloom = BloomFilter::with_params(m, 3).unwrap();
+            bloom.extend(0..100);
+            assert_eq!(bloom.as_raw_slice().len(), bloom.size());
+            assert_eq!(bloom.as_raw_slice(), &bloom.to_bytes()[HEADER_LEN..]);
+
+            let mut copy = BloomFilter::with_params(m, 3).unwrap();
+            copy.merge_raw(bloom.as_raw_slice(), m, 3).unwrap();
+            assert_eq!(copy, bloom);
+        }
+    }
+
+    #[test]
+    fn merge_raw() {
+        let mut a = BloomFilter::new(1_000, 0.01);
+        let mut b = BloomFilter::new(1_000, 0.01);
+        a.extend(0..500);
+        b.extend(500..1_000);
+
+        let bytes = b.to_bytes();
+        let (m, k) = (b.num_bits(), b.num_hash_functions());
+        a.merge_raw(&bytes[HEADER_LEN..], m, k).unwrap();
+        assert!((0..1_000).all(|i| a.contains(&i)));
+
+        let mut expected = BloomFilter::new(1_000, 0.01);
+        expected.extend(0..1_000);
+        assert_eq!(a, expected);
+
+        for (bits, m, k) in [
+            (&bytes[HEADER_LEN..], m + 8, k),
+            (&bytes[HEADER_LEN..], m, k + 1),
+            (&bytes[HEADER_LEN + 1..], m, k),
+        ] {
+            assert_eq!(
+                a.merge_raw(bits, m, k),
+                Err(BloomError::MismatchedParameters)
+            );
+        }
+        assert_eq!(a, expected);
+    }
+
+    #[test]
+    fn union_mismatched() {
+        let mut a = BloomFilter::new(1_000, 0.01);
+        let b = BloomFilter::new(2_000, 0.01);
+        assert_eq!(a.union(&b), Err(BloomError::MismatchedParameters));
+    }
+
+    #[test]
+    fn union_compatible() {
+        let mut small = BloomFilter::with_params(1_000, 4).unwrap();
+        small.extend(0..50);
+        let mut large = BloomFilter::with_params(4_000, 4).unwrap();
+        large.extend(100..150);
+
+        large.union_compatible(&small).unwrap();
+        assert!((0..50).chain(100..150).all(|i| large.contains(&i)));
+        // Each bit of the small filter is set in every tile
+        for i in small.set_bits() {
+            assert!((0..4).all(|tile| large.set_bits().any(|j| j == i + tile * 1_000)));
+        }
+
+        // Filters of the same size merge like `union`, even if designed differently
+        let mut a = BloomFilter::with_params(1_000, 4).unwrap();
+        a.extend(50..100);
+        let mut union = a.clone();
+        union.union_compatible(&small).unwrap();
+        let mut expected = small.clone();
+        expected.union(&a).unwrap();
+        assert_eq!(union, expected);
+    }
+
+    #[test]
+    fn union_compatible_mismatched() {
+        let mut large = BloomFilter::with_params(4_000, 4).unwrap();
+        large.insert(&1);
+        let original = large.clone();
+        let mut small = BloomFilter::with_params(1_000, 4).unwrap();
+
+        let incompatible = [
+            BloomFilter::with_params(3_000, 4).unwrap(),
+            BloomFilter::with_params(1_000, 5).unwrap(),
+            BloomFilter::with_params(8_000, 4).unwrap(),
+        ];
+        for other in &incompatible {
+            assert_eq!(
+                large.union_compatible(other),
+                Err(BloomError::MismatchedParameters)
+            );
+        }
+        let mut seeded = BloomFilter::with_params(1_000, 4).unwrap();
+        seeded.seed = 7;
+        assert_eq!(
+            large.union_compatible(&seeded),
+            Err(BloomError::MismatchedParameters)
+        );
+        assert_eq!(large, original);
+
+        // The larger filter can't be folded into the smaller one
+        assert_eq!(
+            small.union_compatible(&large),
+            Err(BloomError::MismatchedParameters)
+        );
+    }
+
+    #[test]
+    fn intersect() {
+        let mut a = BloomFilter::new(100_000, 0.01);
+        let mut b = BloomFilter::new(100_000, 0.01);
+        a.insert(&"both");
+        a.insert(&"only a");
+        b.insert(&"both");
+        b.insert(&"only b");
+
+        a.intersect(&b).unwrap();
+        assert!(a.contains(&"both"));
+        // Not guaranteed, but overwhelmingly likely for a filter this sparse
+        assert!(!a.contains(&"only a"));
+        assert!(!a.contains(&"only b"));
+    }
+
+    #[test]
+    fn intersect_mismatched() {
+        let mut a = BloomFilter::new(1_000, 0.01);
+        let b = BloomFilter::new(1_000, 0.1);
+        assert_eq!(a.intersect(&b), Err(BloomError::MismatchedParameters));
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        for i in 0..1_000 {
+            bloom.insert(&i);
+        }
+
+        let restored = BloomFilter::from_bytes(&bloom.to_bytes()).unwrap();
+        assert_eq!(restored.num_hash_functions, bloom.num_hash_functions);
+        assert_eq!(restored.bits, bloom.bits);
+        for i in 0..1_000 {
+            assert!(restored.contains(&i));
+        }
+    }
+
+    #[test]
+    fn bytes_round_trip_seeded() {
+        let mut bloom = BloomFilter::new_with_seed(1_000, 0.01, 42);
+        bloom.insert(&"hi");
+
+        let restored = BloomFilter::from_bytes(&bloom.to_bytes()).unwrap();
+        assert_eq!(restored.seed(), 42);
+        assert_eq!(restored.expected_elements(), 1_000);
+        assert_eq!(
+            restored.target_false_positive_rate(),
+            bloom.target_false_positive_rate()
+        );
+        assert_eq!(restored, bloom);
+        assert!(restored.contains(&"hi"));
+    }
+
+    #[test]
+    fn to_bytes_header() {
+        let bytes = BloomFilter::new(1_000, 0.01).to_bytes();
+        assert!(bytes.starts_with(MAGIC));
+        assert_eq!(bytes[MAGIC.len()..][..4], FORMAT_VERSION.to_le_bytes());
+    }
+
+    #[test]
+    fn from_bytes_layout() {
+        // Spelled out byte by byte, so the test doesn't depend on the host's endianness
+        #[rustfmt::skip]
+        let bytes = [
+            b'B', b'L', b'M', b'F',
+            4, 0, 0, 0,
+            20, 0, 0, 0, 0, 0, 0, 0,
+            3, 0, 0, 0, 0, 0, 0, 0,
+            0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01,
+            2, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0xe0, 0x3f,
+            1,
+            0b0000_0101, 0b1000_0000, 0b0000_1111,
+        ];
+
+        let bloom = BloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(bloom.num_bits(), 20);
+        assert_eq!(bloom.num_hash_functions(), 3);
+        assert_eq!(bloom.seed(), 0x0102_0304_0506_0708);
+        assert_eq!(bloom.expected_elements(), 2);
+        assert_eq!(bloom.target_false_positive_rate(), 0.5);
+        assert!(!bloom.enhanced_double_hashing());
+        // Bits are numbered from the least significant bit of each byte
+        assert!(bloom.set_bits().eq([0, 2, 15, 16, 17, 18, 19]));
+        assert_eq!(bloom.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn from_bytes_v1() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        bloom.insert(&"hi");
+
+        // Version 1 headers have no seed
+        let mut bytes = bloom.to_bytes();
+        bytes[4..8].copy_from_slice(&1_u32.to_le_bytes());
+        bytes.drain(HEADER_LEN_V1..HEADER_LEN);
+
+        let restored = BloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.seed(), 0);
+        assert_eq!(restored, bloom);
+        assert!(restored.contains(&"hi"));
+    }
+
+    #[test]
+    fn from_bytes_v2() {
+        let mut bloom = BloomFilter::new_with_seed(1_000, 0.01, 42);
+        bloom.insert(&"hi");
+
+        // Version 2 headers have no design, so it's derived from `m` and `k`
+        let mut bytes = bloom.to_bytes();
+        bytes[4..8].copy_from_slice(&2_u32.to_le_bytes());
+        bytes.drain(HEADER_LEN_V2..HEADER_LEN);
+
+        let restored = BloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.seed(), 42);
+        assert_eq!(restored, bloom);
+        assert_eq!(
+            (
+                restored.expected_elements(),
+                restored.target_false_positive_rate()
+            ),
+            calc_design(bloom.num_bits(), bloom.num_hash_functions())
+        );
+    }
+
+    #[test]
+    fn from_bytes_v3() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        bloom.insert(&"hi");
+
+        // Version 3 headers have no hash scheme, so the first one is assumed
+        let mut bytes = bloom.to_bytes();
+        bytes[4..8].copy_from_slice(&3_u32.to_le_bytes());
+        bytes.remove(HEADER_LEN_V3);
+
+        let restored = BloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(restored, bloom);
+        assert_eq!(restored.expected_elements(), 1_000);
+        assert!(restored.contains(&"hi"));
+    }
+
+    #[test]
+    fn from_bytes_hash_scheme() {
+        let bytes = BloomFilter::new(1_000, 0.01).to_bytes();
+        assert_eq!(bytes[HEADER_LEN - 1], HASH_SCHEME_VERSION);
+
+        // A build on the next scheme rejects filters written under this one, including old
+        // versions that don't record it
+        let mut v3 = bytes.clone();
+        v3[4..8].copy_from_slice(&3_u32.to_le_bytes());
+        v3.remove(HEADER_LEN_V3);
+        for bytes in [&bytes, &v3] {
+            assert_eq!(
+                BloomFilter::from_bytes_for_scheme(bytes, HASH_SCHEME_VERSION + 1),
+                Err(BloomError::UnsupportedHashScheme(HASH_SCHEME_VERSION))
+            );
+        }
+
+        // and this build rejects filters written under the next one
+        let mut next = bytes.clone();
+        next[HEADER_LEN - 1] = HASH_SCHEME_VERSION + 1;
+        assert_eq!(
+            BloomFilter::from_bytes(&next),
+            Err(BloomError::UnsupportedHashScheme(HASH_SCHEME_VERSION + 1))
+        );
+        next[HEADER_LEN - 1] |= ENHANCED_DOUBLE_HASHING;
+        assert_eq!(
+            BloomFilter::from_bytes(&next),
+            Err(BloomError::UnsupportedHashScheme(
+                (HASH_SCHEME_VERSION + 1) | ENHANCED_DOUBLE_HASHING
+            ))
+        );
+    }
+
+    #[test]
+    fn from_bytes_truncated() {
+        let bytes = BloomFilter::new(1_000, 0.01).to_bytes();
+        assert_eq!(
+            BloomFilter::from_bytes(&bytes[..HEADER_LEN - 1]).unwrap_err(),
+            BloomError::UnexpectedEof
+        );
+        assert_eq!(
+            BloomFilter::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            BloomError::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn from_bytes_corrupt() {
+        let mut bytes = BloomFilter::new(1_000, 0.01).to_bytes();
+        bytes.push(0);
+        assert_eq!(
+            BloomFilter::from_bytes(&bytes).unwrap_err(),
+            BloomError::Corrupt
+        );
+
+        let mut bytes = BloomFilter::new(1_000, 0.01).to_bytes();
+        bytes[0] = b'X';
+        assert_eq!(
+            BloomFilter::from_bytes(&bytes).unwrap_err(),
+            BloomError::Corrupt
+        );
+
+        let mut bytes = BloomFilter::new(1_000, 0.01).to_bytes();
+        bytes[4] = 99;
+        assert_eq!(
+            BloomFilter::from_bytes(&bytes).unwrap_err(),
+            BloomError::UnsupportedVersion(99)
+        );
+
+        // Padding bits past `m` must be zero
+        let mut bytes = BloomFilter::with_params(1001, 3).unwrap().to_bytes();
+        *bytes.last_mut().unwrap() |= 0b10;
+        assert_eq!(
+            BloomFilter::from_bytes(&bytes).unwrap_err(),
+            BloomError::Corrupt
+        );
+        *bytes.last_mut().unwrap() = 0b1;
+        assert!(BloomFilter::from_bytes(&bytes).is_ok());
+    }
+
+    #[test]
+    fn from_bytes_huge_bits() {
+        // A header claiming far more bits than follow is rejected before anything is
+        // allocated for them
+        let mut bytes = BloomFilter::new(1_000, 0.01).to_bytes();
+        for m in [u64::MAX, 1 << 62, 1 << 40] {
+            bytes[8..16].copy_from_slice(&m.to_le_bytes());
+            assert_eq!(
+                BloomFilter::from_bytes(&bytes),
+                Err(BloomError::UnexpectedEof)
+            );
+        }
+    }
+
+    #[test]
+    fn from_bytes_huge_k() {
+        // Found by fuzzing: deriving the design of a version 2 filter from a huge `k` hung,
+        // as would every lookup into it
+        let bloom = BloomFilter::new(1_000, 0.01);
+        let mut v2 = bloom.to_bytes();
+        v2[4..8].copy_from_slice(&2_u32.to_le_bytes());
+        v2.drain(HEADER_LEN_V2..HEADER_LEN);
+        for k in [MAX_NUM_HASH_FUNCTIONS as u64 + 1, u64::MAX] {
+            let mut bytes = v2.clone();
+            bytes[16..24].copy_from_slice(&k.to_le_bytes());
+            assert_eq!(BloomFilter::from_bytes(&bytes), Err(BloomError::Corrupt));
+
+            let mut bytes = bloom.to_bytes();
+            bytes[16..24].copy_from_slice(&k.to_le_bytes());
+            assert_eq!(BloomFilter::from_bytes(&bytes), Err(BloomError::Corrupt));
+        }
+    }
+
+    #[test]
+    fn from_bytes_arbitrary() {
+        // A deterministic stand-in for the fuzz target: every truncation and every single
+        // byte corruption of valid filters, and random buffers, parse without panicking
+        let mut bloom = BloomFilter::new_with_seed(10, 0.01, 7);
+        bloom.insert(&"hi");
+        let bytes = bloom.to_bytes();
+        let mut v1 = bytes.clone();
+        v1[4..8].copy_from_slice(&1_u32.to_le_bytes());
+        v1.drain(HEADER_LEN_V1..HEADER_LEN);
+
+        for bytes in [&bytes, &v1] {
+            for len in 0..=bytes.len() {
+                let _ = BloomFilter::from_bytes(&bytes[..len]);
+            }
+            for i in 0..bytes.len() {
+                for value in [0, 1, 0x7f, 0x80, 0xff] {
+                    let mut corrupt = bytes.clone();
+                    corrupt[i] = value;
+                    if let Ok(restored) = BloomFilter::from_bytes(&corrupt) {
+                        assert_eq!(
+                            BloomFilter::from_bytes(&restored.to_bytes()).unwrap(),
+                            restored
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..10_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let mut random = state.to_le_bytes().repeat((state % 16) as usize);
+            let len = random.len().min(MAGIC.len());
+            if state.is_multiple_of(3) {
+                random[..len].copy_from_slice(&MAGIC[..len]);
+            }
+            let _ = BloomFilter::from_bytes(&random);
+        }
+    }
+
+    #[test]
+    fn from_bytes_zero_bits() {
+        // A header claiming no bits, with the bits of a filter after it or none at all
+        let bytes = BloomFilter::new(1_000, 0.01).to_bytes();
+        let mut zero_bits = bytes.clone();
+        zero_bits[8..16].copy_from_slice(&0_u64.to_le_bytes());
+        for bytes in [&zero_bits[..], &zero_bits[..HEADER_LEN]] {
+            assert_eq!(BloomFilter::from_bytes(bytes), Err(BloomError::Corrupt));
+        }
+
+        // Including old versions, whose headers are shorter
+        let mut v1 = zero_bits[..HEADER_LEN_V1].to_vec();
+        v1[4..8].copy_from_slice(&1_u32.to_le_bytes());
+        assert_eq!(BloomFilter::from_bytes(&v1), Err(BloomError::Corrupt));
+    }
+
+    #[test]
+    fn errors() {
+        let bytes = BloomFilter::new(1_000, 0.01).to_bytes();
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 99;
+        let mut a = BloomFilter::new(1_000, 0.01);
+        let b = BloomFilter::new(1_000, 0.1);
+
+        let errors = [
+            BloomFilter::from_bytes(&bytes[..1]).unwrap_err(),
+            BloomFilter::from_bytes(&bad_magic).unwrap_err(),
+            BloomFilter::from_bytes(&bad_version).unwrap_err(),
+            a.union(&b).unwrap_err(),
+            BloomFilter::with_params(0, 1).unwrap_err(),
+        ];
+        for (i, error) in errors.iter().enumerate() {
+            match (i, error) {
+                (0, BloomError::UnexpectedEof) => {}
+                (1, BloomError::Corrupt) => {}
+                (2, BloomError::UnsupportedVersion(99)) => {}
+                (3, BloomError::MismatchedParameters) => {}
+                (4, BloomError::InvalidParameter(msg)) => assert!(msg.contains("num_bits")),
+                _ => panic!("unexpected error {:?} from call {}", error, i),
+            }
+            assert!(!error.to_string().is_empty());
+        }
     }
 
     #[test]
@@ -0,0 +1,176 @@
+//! Counters of a [`BloomFilter`]'s operations, for profiling the cost of hashing. They're
+//! only recorded when the `metrics` feature is enabled; otherwise recording is a no-op and
+//! takes no space in the filter.
+//!
+//! [`BloomFilter`]: crate::BloomFilter
+
+#[cfg(feature = "metrics")]
+use core::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "metrics")]
+use std::time::{Duration, Instant};
+
+/// Aggregate counts and timings of a [`BloomFilter`]'s operations since it was built,
+/// returned by [`BloomFilter::metrics`].
+///
+/// [`BloomFilter`]: crate::BloomFilter
+/// [`BloomFilter::metrics`]: crate::BloomFilter::metrics
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Number of items inserted, including those given by their precomputed hashes.
+    pub inserts: u64,
+
+    /// Number of items looked up, including those given by their precomputed hashes.
+    pub contains: u64,
+
+    /// Number of hashes computed, two per item hashed, or one if the hasher yields both
+    /// halves of a 128-bit hash at once, however many hash functions the filter has.
+    pub hashes: u64,
+
+    /// Total time spent inserting items, hashing included.
+    pub insert_time: Duration,
+
+    /// Total time spent looking up items, hashing included.
+    pub contains_time: Duration,
+}
+
+/// Records a filter's operations.
+///
+/// The counters are atomic so that lookups, which only borrow the filter, can record too.
+#[derive(Debug, Default)]
+pub(crate) struct Recorder {
+    #[cfg(feature = "metrics")]
+    inserts: AtomicU64,
+    #[cfg(feature = "metrics")]
+    contains: AtomicU64,
+    #[cfg(feature = "metrics")]
+    hashes: AtomicU64,
+    #[cfg(feature = "metrics")]
+    insert_nanos: AtomicU64,
+    #[cfg(feature = "metrics")]
+    contains_nanos: AtomicU64,
+}
+
+/// The start of an operation being timed.
+pub(crate) struct Timer {
+    #[cfg(feature = "metrics")]
+    start: Instant,
+}
+
+impl Recorder {
+    /// Start timing an operation.
+    pub(crate) fn start(&self) -> Timer {
+        Timer {
+            #[cfg(feature = "metrics")]
+            start: Instant::now(),
+        }
+    }
+
+    /// Record an insert that started at `timer`.
+    pub(crate) fn record_insert(&self, _timer: Timer) {
+        #[cfg(feature = "metrics")]
+        {
+            self.inserts.fetch_add(1, Ordering::Relaxed);
+            self.insert_nanos
+                .fetch_add(elapsed_nanos(_timer), Ordering::Relaxed);
+        }
+    }
+
+    /// Record `_count` inserts that started together at `timer`.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn record_inserts(&self, _count: u64, _timer: Timer) {
+        #[cfg(feature = "metrics")]
+        {
+            self.inserts.fetch_add(_count, Ordering::Relaxed);
+            self.insert_nanos
+                .fetch_add(elapsed_nanos(_timer), Ordering::Relaxed);
+        }
+    }
+
+    /// Record a lookup that started at `timer`.
+    pub(crate) fn record_contains(&self, _timer: Timer) {
+        #[cfg(feature = "metrics")]
+        {
+            self.contains.fetch_add(1, Ordering::Relaxed);
+            self.contains_nanos
+                .fetch_add(elapsed_nanos(_timer), Ordering::Relaxed);
+        }
+    }
+
+    /// Record that `_count` hashes were computed.
+    pub(crate) fn record_hashes(&self, _count: u64) {
+        #[cfg(feature = "metrics")]
+        self.hashes.fetch_add(_count, Ordering::Relaxed);
+    }
+
+    /// Returns the operations recorded so far.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn snapshot(&self) -> Metrics {
+        Metrics {
+            inserts: self.inserts.load(Ordering::Relaxed),
+            contains: self.contains.load(Ordering::Relaxed),
+            hashes: self.hashes.load(Ordering::Relaxed),
+            insert_time: Duration::from_nanos(self.insert_nanos.load(Ordering::Relaxed)),
+            contains_time: Duration::from_nanos(self.contains_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// A copy of a filter carries on from the operations recorded for the original.
+impl Clone for Recorder {
+    fn clone(&self) -> Self {
+        Recorder {
+            #[cfg(feature = "metrics")]
+            inserts: AtomicU64::new(self.inserts.load(Ordering::Relaxed)),
+            #[cfg(feature = "metrics")]
+            contains: AtomicU64::new(self.contains.load(Ordering::Relaxed)),
+            #[cfg(feature = "metrics")]
+            hashes: AtomicU64::new(self.hashes.load(Ordering::Relaxed)),
+            #[cfg(feature = "metrics")]
+            insert_nanos: AtomicU64::new(self.insert_nanos.load(Ordering::Relaxed)),
+            #[cfg(feature = "metrics")]
+            contains_nanos: AtomicU64::new(self.contains_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Returns the nanoseconds elapsed since `timer` started, saturating at `u64::MAX`.
+#[cfg(feature = "metrics")]
+fn elapsed_nanos(timer: Timer) -> u64 {
+    u64::try_from(timer.start.elapsed().as_nanos()).unwrap_or(u64::MAX)
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn counters() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        assert_eq!(bloom.metrics(), Metrics::default());
+
+        bloom.insert(&"hi");
+        bloom.insert_all(&["a", "b"]);
+        bloom.insert_hashes(1, 2);
+        assert!(bloom.contains(&"hi"));
+        assert!(bloom.contains_hashes(1, 2));
+        bloom.insert_bytes(b"yo");
+        assert!(bloom.contains_bytes(b"yo"));
+
+        let metrics = bloom.metrics();
+        assert_eq!(metrics.inserts, 5);
+        assert_eq!(metrics.contains, 3);
+        // Items given by their hashes aren't hashed again, and each other item is hashed
+        // twice however many hash functions there are
+        assert_eq!(metrics.hashes, 2 * 6);
+        assert_eq!(bloom.clone().metrics(), metrics);
+    }
+
+    #[test]
+    fn sip128_hashes_once() {
+        let mut bloom = BloomFilter::with_hasher(1_000, 0.01, Sip128HashBuilder::default());
+        bloom.insert(&"hi");
+        assert!(bloom.contains(&"hi"));
+        assert_eq!(bloom.metrics().hashes, 2);
+    }
+}
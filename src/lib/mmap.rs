@@ -0,0 +1,153 @@
+//! A read-only [`BloomFilter`] queried straight from a memory-mapped file, with the `mmap`
+//! feature.
+//!
+//! [`BloomFilter`]: crate::BloomFilter
+
+use crate::{
+    calc_index, calc_index_enhanced, hash_pair, parse_header, BloomFilter, DefaultHashBuilder,
+    HASH_SCHEME_VERSION,
+};
+use core::hash::Hash;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+impl BloomFilter {
+    /// Memory-map a Bloom filter file written with [`BloomFilter::to_bytes`], to query it
+    /// without reading it into memory.
+    ///
+    /// Only the header is read up front; the bits are paged in from the file as lookups
+    /// touch them, so even a filter of many gigabits opens instantly and only takes as much
+    /// memory as the pages its lookups hit. The mapped filter is read-only.
+    ///
+    /// Returns an [`io::ErrorKind::InvalidData`] error wrapping the [`BloomError`] if the
+    /// file isn't a filter [`BloomFilter::from_bytes`] would load.
+    ///
+    /// The file must not be modified or truncated while it's mapped: the mapping would see
+    /// the changes, and lookups could return wrong results or crash the process.
+    ///
+    /// [`BloomError`]: crate::BloomError
+    pub fn open_mmap(path: &Path) -> io::Result<MmapBloomFilter> {
+        let file = File::open(path)?;
+        // Safety: as documented, the file must not be modified while it's mapped
+        let mmap = unsafe { Mmap::map(&file)? };
+        let (header, raw) = parse_header(&mmap, HASH_SCHEME_VERSION)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(MmapBloomFilter {
+            bits_offset: mmap.len() - raw.len(),
+            num_bits: header.num_bits,
+            num_hash_functions: header.num_hash_functions,
+            seed: header.seed,
+            enhanced_double_hashing: header.enhanced_double_hashing,
+            hash_builder: DefaultHashBuilder::default(),
+            mmap,
+        })
+    }
+}
+
+/// A read-only Bloom filter backed by a memory-mapped file, returned by
+/// [`BloomFilter::open_mmap`].
+///
+/// It answers lookups like the [`BloomFilter`] the file was written from, but has no
+/// methods to insert items.
+///
+/// [`BloomFilter`]: crate::BloomFilter
+/// [`BloomFilter::open_mmap`]: crate::BloomFilter::open_mmap
+#[derive(Debug)]
+pub struct MmapBloomFilter {
+    // Serialized filter, whose bit array starts at `bits_offset`
+    mmap: Mmap,
+    bits_offset: usize,
+
+    num_bits: usize,
+    num_hash_functions: usize,
+    seed: u64,
+    enhanced_double_hashing: bool,
+    hash_builder: DefaultHashBuilder,
+}
+
+impl MmapBloomFilter {
+    /// Returns the number of bits of the filter.
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+
+    /// Returns the number of hash functions of the filter.
+    pub fn num_hash_functions(&self) -> usize {
+        self.num_hash_functions
+    }
+
+    /// Returns the seed mixed into the hashes of every item.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns whether bit indices are derived by enhanced double hashing.
+    pub fn enhanced_double_hashing(&self) -> bool {
+        self.enhanced_double_hashing
+    }
+
+    /// Returns whether the Bloom filter contains the item. It may return a false positive
+    /// but will never return a false negative.
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        let (h1, h2) = hash_pair(&self.hash_builder, self.seed, item);
+        let bits = &self.mmap[self.bits_offset..];
+        (0..self.num_hash_functions).all(|i| {
+            let b = if self.enhanced_double_hashing {
+                calc_index_enhanced(h1, h2, i, self.num_bits)
+            } else {
+                calc_index(h1, h2, i, self.num_bits)
+            };
+            // Bit `b` is bit `b % 8` of byte `b / 8`, as serialized by `to_bytes`
+            bits[b / 8] & (1 << (b % 8)) != 0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn open_mmap() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("words.bloom");
+        for enhanced in [false, true] {
+            let mut bloom = BloomFilter::builder()
+                .expected_items(1_000)
+                .seed(42)
+                .enhanced_double_hashing(enhanced)
+                .build()
+                .unwrap();
+            bloom.insert_all(&["hi", "yo", "sup"]);
+            std::fs::write(&path, bloom.to_bytes()).unwrap();
+
+            let mapped = BloomFilter::open_mmap(&path).unwrap();
+            assert_eq!(mapped.num_bits(), bloom.num_bits());
+            assert_eq!(mapped.num_hash_functions(), bloom.num_hash_functions());
+            assert_eq!(mapped.seed(), 42);
+            assert_eq!(mapped.enhanced_double_hashing(), enhanced);
+            for item in ["hi", "yo", "sup"] {
+                assert!(mapped.contains(item));
+            }
+            for i in 0..10_000 {
+                assert_eq!(mapped.contains(&i), bloom.contains(&i));
+            }
+        }
+    }
+
+    #[test]
+    fn open_mmap_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("garbage.bloom");
+        std::fs::write(&path, b"not a filter").unwrap();
+        let err = BloomFilter::open_mmap(&path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let missing = dir.path().join("missing.bloom");
+        let err = BloomFilter::open_mmap(&missing).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+}
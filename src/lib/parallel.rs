@@ -0,0 +1,137 @@
+//! Parallel bulk insertion into a [`BloomFilter`], with the `rayon` feature.
+//!
+//! [`BloomFilter`]: crate::BloomFilter
+
+use crate::{calc_index, calc_index_enhanced, hash_pair, hashes_per_item, BloomFilter};
+use bitvec::mem::{bits_of, BitRegister};
+use bitvec::prelude::*;
+use bitvec::store::BitStore;
+use core::hash::{BuildHasher, Hash};
+use rayon::prelude::*;
+
+// Number of items hashed per batch, bounding the memory holding their bit indices
+const BATCH_LEN: usize = 1 << 16;
+
+impl<S: BuildHasher + Sync, W: BitRegister + BitStore + Send> BloomFilter<S, W>
+where
+    S::Hasher: 'static,
+{
+    /// Insert every item of a slice into the Bloom filter like [`BloomFilter::insert_all`],
+    /// spreading the work over rayon's thread pool.
+    ///
+    /// Items are inserted in batches. Each batch is hashed in parallel into pairs of a bit
+    /// index and the position of the item it came from, which are then sorted in parallel.
+    /// The bit vector is split into disjoint runs of whole storage words, one per task, and
+    /// each task sets the bits of the indices falling within its run, found by binary search.
+    /// No two tasks ever touch the same word, so the bits are set without locks or atomic
+    /// operations, and the filter ends up exactly as if the items had been inserted one by
+    /// one, including the count of inserts that set new bits that
+    /// [`BloomFilter::is_saturated`] relies on.
+    ///
+    /// Sorting the indices costs more than setting them directly: on a single thread this is
+    /// several times slower than [`BloomFilter::insert_all`], so it only pays off with enough
+    /// cores to make up for that, and more so for items that are slow to hash (`cargo bench
+    /// --bench parallel --features rayon`).
+    pub fn par_insert_all<T: Hash + Sync>(&mut self, items: &[T]) {
+        let timer = self.metrics.start();
+        for batch in items.chunks(BATCH_LEN) {
+            self.par_insert_batch(batch);
+        }
+        self.metrics.record_inserts(items.len() as u64, timer);
+    }
+
+    fn par_insert_batch<T: Hash + Sync>(&mut self, batch: &[T]) {
+        let (hash_builder, seed, metrics) = (&self.hash_builder, self.seed, &self.metrics);
+        let (k, len, enhanced) = (
+            self.num_hash_functions,
+            self.bits.len(),
+            self.enhanced_double_hashing,
+        );
+
+        // Sorted by bit index, then by item, so the first pair of each index is the earliest
+        // item setting it, the one that sets it if it's new
+        let mut indices: Vec<(usize, usize)> = batch
+            .par_iter()
+            .enumerate()
+            .flat_map_iter(|(item_num, item)| {
+                metrics.record_hashes(hashes_per_item::<S>());
+                let (h1, h2) = hash_pair(hash_builder, seed, item);
+                (0..k).map(move |i| {
+                    let b = if enhanced {
+                        calc_index_enhanced(h1, h2, i, len)
+                    } else {
+                        calc_index(h1, h2, i, len)
+                    };
+                    (b, item_num)
+                })
+            })
+            .collect();
+        indices.par_sort_unstable();
+
+        let words = self.bits.as_raw_mut_slice();
+        let run_len = words
+            .len()
+            .div_ceil(rayon::current_num_threads() * 4)
+            .max(1);
+        let mut new_items: Vec<usize> = words
+            .par_chunks_mut(run_len)
+            .enumerate()
+            .flat_map_iter(|(run, words)| {
+                let start = run * run_len * bits_of::<W>();
+                let end = start + words.len() * bits_of::<W>();
+                let lo = indices.partition_point(|&(b, _)| b < start);
+                let hi = indices.partition_point(|&(b, _)| b < end);
+                let bits = BitSlice::<W, Lsb0>::from_slice_mut(words);
+                indices[lo..hi]
+                    .iter()
+                    .filter(|&&(b, _)| !bits.replace(b - start, true))
+                    .map(|&(_, item_num)| item_num)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        // Items that set several new bits are counted once
+        new_items.par_sort_unstable();
+        new_items.dedup();
+        self.inserted += new_items.len() as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn matches_sequential() {
+        let items: Vec<String> = (0..200_000).map(|i| format!("item{}", i)).collect();
+        for enhanced in [false, true] {
+            let mut sequential = BloomFilter::builder()
+                .expected_items(100_000)
+                .seed(7)
+                .enhanced_double_hashing(enhanced)
+                .storage::<u32>()
+                .build()
+                .unwrap();
+            let mut parallel = sequential.clone();
+
+            sequential.insert_all(&items);
+            parallel.par_insert_all(&items);
+            assert_eq!(parallel, sequential);
+            assert_eq!(parallel.inserted, sequential.inserted);
+            assert!(items.iter().all(|item| parallel.contains(item)));
+        }
+    }
+
+    #[test]
+    fn duplicates() {
+        let items: Vec<u64> = (0..1_000).chain(0..1_000).collect();
+        let mut sequential = BloomFilter::new(10_000, 0.01);
+        sequential.insert(&3_u64);
+        let mut parallel = sequential.clone();
+
+        sequential.insert_all(&items);
+        parallel.par_insert_all(&items);
+        assert_eq!(parallel, sequential);
+        assert_eq!(parallel.inserted, sequential.inserted);
+    }
+}
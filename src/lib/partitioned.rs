@@ -0,0 +1,174 @@
+//! A partitioned Bloom filter, in which each hash function owns a slice of the bits.
+
+use crate::{calc_index, calc_k, calc_m, hash_pair, DefaultHashBuilder};
+use bitvec::prelude::*;
+use core::hash::{BuildHasher, Hash};
+
+/// Partitioned Bloom filter data structure.
+///
+/// The *`m`* bits are divided into *`k`* equal partitions and the *`i`*-th hash function
+/// only ever sets a bit within partition *`i`*. Every item therefore sets exactly *`k`*
+/// distinct bits, one per partition, and each partition fills like an array of *`m/k`*
+/// slots receiving one ball per item, which gives
+/// [`PartitionedBloomFilter::estimate_count`] a simple closed form.
+///
+/// Its false positive rate is asymptotically the same as a [`BloomFilter`] of the same size
+/// and *`k`*.
+///
+/// [`BloomFilter`]: crate::BloomFilter
+#[derive(Debug, Clone)]
+pub struct PartitionedBloomFilter<S = DefaultHashBuilder> {
+    // Number of hash functions, and so of partitions
+    num_hash_functions: usize,
+
+    // Number of bits in each partition
+    partition_bits: usize,
+
+    // Bit vector storing the partitions one after the other
+    bits: BitVec<u8>,
+
+    // Builds the hashers applied to items
+    hash_builder: S,
+}
+
+impl PartitionedBloomFilter {
+    /// Instantiate a new [`PartitionedBloomFilter`] by providing the expected `num_elements`
+    /// that will be added to the filter and the target `false_positive_rate`.
+    ///
+    /// [`PartitionedBloomFilter`]: PartitionedBloomFilter
+    pub fn new(num_elements: usize, false_positive_rate: f32) -> PartitionedBloomFilter {
+        PartitionedBloomFilter::with_hasher(
+            num_elements,
+            false_positive_rate,
+            DefaultHashBuilder::default(),
+        )
+    }
+}
+
+impl<S: BuildHasher> PartitionedBloomFilter<S>
+where
+    S::Hasher: 'static,
+{
+    /// Instantiate a new [`PartitionedBloomFilter`] like [`PartitionedBloomFilter::new`],
+    /// hashing items with hashers built by `hash_builder`.
+    ///
+    /// [`PartitionedBloomFilter`]: PartitionedBloomFilter
+    pub fn with_hasher(
+        num_elements: usize,
+        false_positive_rate: f32,
+        hash_builder: S,
+    ) -> PartitionedBloomFilter<S> {
+        let m = calc_m(num_elements, false_positive_rate);
+        let k = calc_k(num_elements, m);
+        // Round up so that the partitions are equal
+        let partition_bits = m.div_ceil(k);
+
+        PartitionedBloomFilter {
+            num_hash_functions: k,
+            partition_bits,
+            bits: bitvec![u8, Lsb0; 0; partition_bits * k],
+            hash_builder,
+        }
+    }
+
+    /// Returns the number of hash functions, `k`, which is also the number of partitions.
+    pub fn num_hash_functions(&self) -> usize {
+        self.num_hash_functions
+    }
+
+    /// Returns the size in bits of the filter, `m`.
+    pub fn num_bits(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Returns the size in bits of each partition, `m/k`.
+    pub fn partition_bits(&self) -> usize {
+        self.partition_bits
+    }
+
+    /// Returns an estimate of the number of distinct items inserted into the filter.
+    ///
+    /// Each item sets one bit in each partition, so with *`X`* bits set over all
+    /// partitions of *`p`* bits each, the estimate is *`ln(1 - X/m)/ln(1 - 1/p)`*.
+    pub fn estimate_count(&self) -> usize {
+        let fill = self.bits.count_ones() as f64 / self.bits.len() as f64;
+        let p = self.partition_bits as f64;
+        ((1.0 - fill).ln() / (1.0 - 1.0 / p).ln()).round() as usize
+    }
+
+    /// Insert an item into the filter, setting one bit in each of the *`k`* partitions.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        let (h1, h2) = hash_pair(&self.hash_builder, 0, item);
+        for i in 0..self.num_hash_functions {
+            let bit = self.calc_bit(h1, h2, i);
+            self.bits.set(bit, true);
+        }
+    }
+
+    /// Returns whether the filter contains the item. It may return a false positive but will
+    /// never return a false negative.
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        let (h1, h2) = hash_pair(&self.hash_builder, 0, item);
+        (0..self.num_hash_functions).all(|i| self.bits[self.calc_bit(h1, h2, i)])
+    }
+
+    /// Calculate the index of the bit the `hash_func_num`-th hash function sets, within
+    /// partition `hash_func_num`.
+    fn calc_bit(&self, h1: u64, h2: u64, hash_func_num: usize) -> usize {
+        hash_func_num * self.partition_bits + calc_index(h1, h2, hash_func_num, self.partition_bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::partitioned::*;
+
+    #[test]
+    fn equal_partitions() {
+        let bloom = PartitionedBloomFilter::new(1_000, 0.01);
+        assert_eq!(
+            bloom.num_bits(),
+            bloom.partition_bits() * bloom.num_hash_functions()
+        );
+        assert!(bloom.num_bits() >= calc_m(1_000, 0.01));
+    }
+
+    #[test]
+    fn insert_sets_k_bits() {
+        for item in ["hi", "yo", "sup"] {
+            let mut bloom = PartitionedBloomFilter::new(100, 0.01);
+            bloom.insert(&item);
+            assert_eq!(bloom.bits.count_ones(), bloom.num_hash_functions());
+            for partition in bloom.bits.chunks(bloom.partition_bits()) {
+                assert_eq!(partition.count_ones(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn no_false_negatives() {
+        let mut bloom = PartitionedBloomFilter::new(10_000, 0.01);
+        for i in 0..10_000 {
+            bloom.insert(&i);
+        }
+        for i in 0..10_000 {
+            assert!(bloom.contains(&i));
+        }
+        let false_positives = (10_000..110_000).filter(|i| bloom.contains(i)).count();
+        assert!(
+            false_positives < 1_200,
+            "false_positives={}",
+            false_positives
+        );
+    }
+
+    #[test]
+    fn estimate_count() {
+        let mut bloom = PartitionedBloomFilter::new(100_000, 0.01);
+        for i in 0..100_000 {
+            bloom.insert(&i);
+        }
+        let estimate = bloom.estimate_count() as f64;
+        assert!((estimate - 100_000.0).abs() / 100_000.0 < 0.02);
+    }
+}
@@ -0,0 +1,132 @@
+//! Export of a [`BloomFilter`]'s set bits as a Roaring bitmap, enabled by the `roaring`
+//! feature.
+//!
+//! The bitmap is written in the portable [Roaring format] shared by the Java, C, Go and
+//! Rust implementations, without run containers: a cookie and the number of containers,
+//! the key and cardinality of each container, their offsets, and then the containers
+//! themselves. A container holds the set bits whose index shares its upper 16 bits, the key,
+//! as a sorted array of their lower 16 bits if there are at most 4096 of them, or as a
+//! 65536-bit bitmap otherwise.
+//!
+//! [`BloomFilter`]: crate::BloomFilter
+//! [Roaring format]: https://github.com/RoaringBitmap/RoaringFormatSpec
+
+use crate::BloomFilter;
+use alloc::vec::Vec;
+use bitvec::mem::BitRegister;
+use bitvec::store::BitStore;
+use core::hash::BuildHasher;
+
+// Cookie of a bitmap without run containers, followed by its number of containers
+const SERIAL_COOKIE_NO_RUNCONTAINER: u32 = 12346;
+
+// Largest number of values held in an array container rather than a bitmap container
+const ARRAY_CONTAINER_MAX: usize = 4096;
+
+// Number of `u64` words of a bitmap container
+const BITMAP_CONTAINER_WORDS: usize = 1024;
+
+impl<S: BuildHasher, W: BitRegister + BitStore> BloomFilter<S, W>
+where
+    S::Hasher: 'static,
+{
+    /// Serialize the indices of the bits currently set, as yielded by
+    /// [`BloomFilter::set_bits`], into the portable Roaring bitmap format, for use with
+    /// analytics tooling.
+    ///
+    /// Roaring is far more compact than the packed bit array of [`BloomFilter::to_bytes`]
+    /// for sparsely populated filters. Only the bits are written, not the filter's
+    /// parameters, so it can't be loaded back as a filter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the filter has more than `2^32` bits, as Roaring bitmaps hold `u32`s.
+    pub fn to_roaring_bytes(&self) -> Vec<u8> {
+        assert!(
+            self.bits.len() as u64 <= 1 << 32,
+            "filters of more than 2^32 bits don't fit in a Roaring bitmap"
+        );
+
+        // Lower 16 bits of the set bits, grouped by their upper 16 bits
+        let mut containers: Vec<(u16, Vec<u16>)> = Vec::new();
+        for i in self.bits.iter_ones() {
+            let (key, low) = ((i >> 16) as u16, i as u16);
+            match containers.last_mut() {
+                Some((last, values)) if *last == key => values.push(low),
+                _ => containers.push((key, alloc::vec![low])),
+            }
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&SERIAL_COOKIE_NO_RUNCONTAINER.to_le_bytes());
+        bytes.extend_from_slice(&(containers.len() as u32).to_le_bytes());
+        for (key, values) in &containers {
+            bytes.extend_from_slice(&key.to_le_bytes());
+            bytes.extend_from_slice(&((values.len() - 1) as u16).to_le_bytes());
+        }
+        let mut offset = bytes.len() + 4 * containers.len();
+        for (_, values) in &containers {
+            bytes.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += container_len(values.len());
+        }
+        for (_, values) in &containers {
+            if values.len() <= ARRAY_CONTAINER_MAX {
+                for value in values {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            } else {
+                let mut words = [0_u64; BITMAP_CONTAINER_WORDS];
+                for &value in values {
+                    words[value as usize / 64] |= 1 << (value % 64);
+                }
+                for word in words {
+                    bytes.extend_from_slice(&word.to_le_bytes());
+                }
+            }
+        }
+        bytes
+    }
+}
+
+/// Returns the length in bytes of a container holding `cardinality` values.
+fn container_len(cardinality: usize) -> usize {
+    if cardinality <= ARRAY_CONTAINER_MAX {
+        2 * cardinality
+    } else {
+        8 * BITMAP_CONTAINER_WORDS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::roaring_impl::*;
+    use roaring::RoaringBitmap;
+
+    fn round_trip(bloom: &BloomFilter) {
+        let bitmap = RoaringBitmap::deserialize_from(&bloom.to_roaring_bytes()[..]).unwrap();
+        assert!(bitmap.iter().map(|i| i as usize).eq(bloom.set_bits()));
+    }
+
+    #[test]
+    fn empty() {
+        let bloom = BloomFilter::new(1_000, 0.01);
+        round_trip(&bloom);
+        assert_eq!(bloom.to_roaring_bytes().len(), 8);
+    }
+
+    #[test]
+    fn sparse() {
+        let mut bloom = BloomFilter::new(1_000_000, 0.01);
+        bloom.extend(0..100);
+        round_trip(&bloom);
+        assert!(bloom.to_roaring_bytes().len() * 10 < bloom.size());
+    }
+
+    #[test]
+    fn dense() {
+        // Saturated containers are written as bitmaps
+        let mut bloom = BloomFilter::from_bit_count(200_000, 4);
+        bloom.extend(0..50_000);
+        round_trip(&bloom);
+    }
+}
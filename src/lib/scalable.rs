@@ -0,0 +1,192 @@
+//! A scalable Bloom filter, which grows as items are inserted.
+
+use crate::{BloomFilter, DefaultHashBuilder};
+use core::hash::{BuildHasher, Hash};
+
+// Each new sub-filter is sized for this many times the previous one's capacity
+const GROWTH_FACTOR: usize = 2;
+
+// Each new sub-filter's false positive rate is this many times the previous one's
+const TIGHTENING_RATIO: f32 = 0.5;
+
+/// Scalable Bloom filter data structure.
+///
+/// A [scalable Bloom filter](https://doi.org/10.1016/j.ipl.2006.10.007) doesn't need to
+/// know the number of elements ahead of time. It starts with a single [`BloomFilter`] sized
+/// for `initial_capacity` elements and, whenever the active sub-filter fills up to the point
+/// where its false positive rate reaches its own target, adds a new one with twice the
+/// capacity. Each new sub-filter has half the
+/// false positive rate of the previous one, bounding the compounded false positive rate of
+/// the whole filter by the target `false_positive_rate`.
+///
+/// [`BloomFilter`]: crate::BloomFilter
+#[derive(Debug, Clone)]
+pub struct ScalableBloomFilter<S = DefaultHashBuilder> {
+    // Sub-filters, the last of which is active
+    filters: Vec<BloomFilter<S>>,
+
+    // Number of bits set in the active sub-filter, and the number at which it is full
+    active_ones: usize,
+    active_max_ones: usize,
+
+    // Capacity and false positive rate of the first sub-filter
+    initial_capacity: usize,
+    initial_false_positive_rate: f32,
+
+    // Number of items inserted that weren't already contained
+    len: usize,
+
+    // Builds the hashers applied to items, cloned into each sub-filter
+    hash_builder: S,
+}
+
+impl ScalableBloomFilter {
+    /// Instantiate a new [`ScalableBloomFilter`] by providing the `initial_capacity` of its
+    /// first sub-filter and the target `false_positive_rate` of the whole filter.
+    ///
+    /// [`ScalableBloomFilter`]: ScalableBloomFilter
+    pub fn new(initial_capacity: usize, false_positive_rate: f32) -> ScalableBloomFilter {
+        ScalableBloomFilter::with_hasher(
+            initial_capacity,
+            false_positive_rate,
+            DefaultHashBuilder::default(),
+        )
+    }
+}
+
+impl<S: BuildHasher + Clone> ScalableBloomFilter<S>
+where
+    S::Hasher: 'static,
+{
+    /// Instantiate a new [`ScalableBloomFilter`] like [`ScalableBloomFilter::new`], hashing
+    /// items with hashers built by `hash_builder`.
+    ///
+    /// [`ScalableBloomFilter`]: ScalableBloomFilter
+    pub fn with_hasher(
+        initial_capacity: usize,
+        false_positive_rate: f32,
+        hash_builder: S,
+    ) -> ScalableBloomFilter<S> {
+        // The sub-filters' rates form a geometric series summing to at most
+        // `false_positive_rate`
+        let initial_false_positive_rate = false_positive_rate * (1.0 - TIGHTENING_RATIO);
+
+        let mut bloom = ScalableBloomFilter {
+            filters: Vec::new(),
+            active_ones: 0,
+            active_max_ones: 0,
+            initial_capacity,
+            initial_false_positive_rate,
+            len: 0,
+            hash_builder,
+        };
+        bloom.grow();
+        bloom
+    }
+
+    /// Returns the estimated number of distinct items inserted into the filter.
+    ///
+    /// Counts inserted items that weren't already reported as contained, so it undercounts
+    /// by the number of false positives encountered while inserting.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether no items have been inserted into the filter.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of sub-filters allocated so far.
+    pub fn num_filters(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Insert an item into the filter.
+    ///
+    /// Items already reported as contained are skipped. Otherwise the item is inserted into
+    /// the active sub-filter, first allocating a new one if the active sub-filter is full.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        if self.contains(item) {
+            return;
+        }
+
+        if self.active_ones >= self.active_max_ones {
+            self.grow();
+        }
+        self.active_ones += self.filters.last_mut().unwrap().insert_and_report(item);
+        self.len += 1;
+    }
+
+    /// Returns whether any of the sub-filters contains the item. It may return a false
+    /// positive but will never return a false negative.
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        self.filters.iter().any(|filter| filter.contains(item))
+    }
+
+    /// Allocate a new active sub-filter with a larger capacity and tighter false positive
+    /// rate than the previous one.
+    fn grow(&mut self) {
+        let i = self.filters.len() as i32;
+        let capacity = self.initial_capacity * GROWTH_FACTOR.pow(i as u32);
+        let false_positive_rate = self.initial_false_positive_rate * TIGHTENING_RATIO.powi(i);
+
+        let filter =
+            BloomFilter::with_hasher(capacity, false_positive_rate, self.hash_builder.clone());
+
+        // The filter's false positive rate is (X/m)^k, which reaches its target once
+        // X = m * f^(1/k) bits are set
+        let max_fill_ratio =
+            (false_positive_rate as f64).powf(1.0 / filter.num_hash_functions() as f64);
+        self.active_max_ones = (max_fill_ratio * filter.num_bits() as f64) as usize;
+        self.active_ones = 0;
+        self.filters.push(filter);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scalable::*;
+
+    #[test]
+    fn starts_with_one_filter() {
+        let bloom = ScalableBloomFilter::new(1_000, 0.01);
+        assert_eq!(bloom.num_filters(), 1);
+        assert!(bloom.is_empty());
+    }
+
+    #[test]
+    fn grows() {
+        let mut bloom = ScalableBloomFilter::new(100, 0.01);
+        for i in 0..10_000 {
+            bloom.insert(&i);
+        }
+        assert!(bloom.num_filters() > 1);
+        for i in 0..10_000 {
+            assert!(bloom.contains(&i));
+        }
+    }
+
+    #[test]
+    fn compounded_false_positive_rate() {
+        let mut bloom = ScalableBloomFilter::new(1_000, 0.01);
+        for i in 0..50_000 {
+            bloom.insert(&i);
+        }
+        assert!(bloom.num_filters() > 1);
+        let false_positives = (50_000..150_000).filter(|i| bloom.contains(i)).count();
+        let rate = false_positives as f64 / 100_000.0;
+        assert!(rate < 0.01, "rate={}", rate);
+    }
+
+    #[test]
+    fn len() {
+        let mut bloom = ScalableBloomFilter::new(100, 0.01);
+        for i in 0..1_000 {
+            bloom.insert(&i);
+            bloom.insert(&i);
+        }
+        let len = bloom.len() as f64;
+        assert!((len - 1_000.0).abs() / 1_000.0 < 0.05, "len={}", len);
+    }
+}
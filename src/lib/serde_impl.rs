@@ -0,0 +1,507 @@
+//! [`serde`] support for [`BloomFilter`], enabled by the `serde` feature.
+//!
+//! A filter is serialized as a struct with seven fields: `num_hash_functions`, `num_bits`
+//! (the exact bit length `m`), `bits` (the packed bit array as bytes), `seed`,
+//! `expected_elements`, `target_false_positive_rate` and `hash_scheme` (the version of the
+//! scheme deriving items' bits from their hashes, with its high bit set for filters using
+//! enhanced double hashing). The last four are optional when
+//! deserializing, so filters serialized before they were introduced can still be read:
+//! `seed` defaults to `0`, the design to the one `m` and `k` are optimal for and the hash
+//! scheme to the first one. Filters written under another hash scheme than this build's are
+//! rejected, since they can't be queried correctly.
+//!
+//! [`BloomFilter`]: crate::BloomFilter
+
+use crate::{
+    calc_design, hash_scheme, BloomFilter, ENHANCED_DOUBLE_HASHING, HASH_SCHEME_VERSION,
+    MAX_NUM_HASH_FUNCTIONS,
+};
+use alloc::vec::Vec;
+use bitvec::prelude::*;
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+const FIELDS: &[&str] = &[
+    "num_hash_functions",
+    "num_bits",
+    "bits",
+    "seed",
+    "expected_elements",
+    "target_false_positive_rate",
+    "hash_scheme",
+];
+
+impl<S> Serialize for BloomFilter<S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut state = serializer.serialize_struct("BloomFilter", FIELDS.len())?;
+        state.serialize_field("num_hash_functions", &(self.num_hash_functions as u64))?;
+        state.serialize_field("num_bits", &(self.bits.len() as u64))?;
+        state.serialize_field("bits", self.bits.as_raw_slice())?;
+        state.serialize_field("seed", &self.seed)?;
+        state.serialize_field("expected_elements", &(self.expected_elements as u64))?;
+        state.serialize_field(
+            "target_false_positive_rate",
+            &self.target_false_positive_rate,
+        )?;
+        state.serialize_field("hash_scheme", &hash_scheme(self.enhanced_double_hashing))?;
+        state.end()
+    }
+}
+
+/// Deserialized filters hash items with `S::default()`, which must match the hasher the
+/// filter was serialized with.
+impl<'de, S: Default> Deserialize<'de> for BloomFilter<S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct("BloomFilter", FIELDS, BloomFilterVisitor(PhantomData))
+    }
+}
+
+enum Field {
+    NumHashFunctions,
+    NumBits,
+    Bits,
+    Seed,
+    ExpectedElements,
+    TargetFalsePositiveRate,
+    HashScheme,
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldVisitor;
+
+        impl<'de> Visitor<'de> for FieldVisitor {
+            type Value = Field;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(
+                    "`num_hash_functions`, `num_bits`, `bits`, `seed`, `expected_elements`, \
+                     `target_false_positive_rate` or `hash_scheme`",
+                )
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Field, E> {
+                match value {
+                    "num_hash_functions" => Ok(Field::NumHashFunctions),
+                    "num_bits" => Ok(Field::NumBits),
+                    "bits" => Ok(Field::Bits),
+                    "seed" => Ok(Field::Seed),
+                    "expected_elements" => Ok(Field::ExpectedElements),
+                    "target_false_positive_rate" => Ok(Field::TargetFalsePositiveRate),
+                    "hash_scheme" => Ok(Field::HashScheme),
+                    _ => Err(de::Error::unknown_field(value, FIELDS)),
+                }
+            }
+        }
+
+        deserializer.deserialize_identifier(FieldVisitor)
+    }
+}
+
+struct BloomFilterVisitor<S>(PhantomData<S>);
+
+impl<'de, S: Default> Visitor<'de> for BloomFilterVisitor<S> {
+    type Value = BloomFilter<S>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("struct BloomFilter")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<BloomFilter<S>, A::Error> {
+        let num_hash_functions = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let num_bits = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let bits = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        let seed = seq.next_element()?.unwrap_or(0);
+        let expected_elements = seq.next_element()?;
+        let target_false_positive_rate = match expected_elements {
+            Some(_) => Some(
+                seq.next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(5, &self))?,
+            ),
+            None => None,
+        };
+        let hash_scheme = seq.next_element()?.unwrap_or(1);
+        build(
+            num_hash_functions,
+            num_bits,
+            bits,
+            seed,
+            expected_elements,
+            target_false_positive_rate,
+            hash_scheme,
+        )
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<BloomFilter<S>, A::Error> {
+        let mut num_hash_functions = None;
+        let mut num_bits = None;
+        let mut bits = None;
+        let mut seed = None;
+        let mut expected_elements = None;
+        let mut target_false_positive_rate = None;
+        let mut hash_scheme = None;
+        while let Some(key) = map.next_key()? {
+            match key {
+                Field::NumHashFunctions => {
+                    if num_hash_functions.is_some() {
+                        return Err(de::Error::duplicate_field("num_hash_functions"));
+                    }
+                    num_hash_functions = Some(map.next_value()?);
+                }
+                Field::NumBits => {
+                    if num_bits.is_some() {
+                        return Err(de::Error::duplicate_field("num_bits"));
+                    }
+                    num_bits = Some(map.next_value()?);
+                }
+                Field::Bits => {
+                    if bits.is_some() {
+                        return Err(de::Error::duplicate_field("bits"));
+                    }
+                    bits = Some(map.next_value()?);
+                }
+                Field::Seed => {
+                    if seed.is_some() {
+                        return Err(de::Error::duplicate_field("seed"));
+                    }
+                    seed = Some(map.next_value()?);
+                }
+                Field::ExpectedElements => {
+                    if expected_elements.is_some() {
+                        return Err(de::Error::duplicate_field("expected_elements"));
+                    }
+                    expected_elements = Some(map.next_value()?);
+                }
+                Field::TargetFalsePositiveRate => {
+                    if target_false_positive_rate.is_some() {
+                        return Err(de::Error::duplicate_field("target_false_positive_rate"));
+                    }
+                    target_false_positive_rate = Some(map.next_value()?);
+                }
+                Field::HashScheme => {
+                    if hash_scheme.is_some() {
+                        return Err(de::Error::duplicate_field("hash_scheme"));
+                    }
+                    hash_scheme = Some(map.next_value()?);
+                }
+            }
+        }
+        build(
+            num_hash_functions.ok_or_else(|| de::Error::missing_field("num_hash_functions"))?,
+            num_bits.ok_or_else(|| de::Error::missing_field("num_bits"))?,
+            bits.ok_or_else(|| de::Error::missing_field("bits"))?,
+            seed.unwrap_or(0),
+            expected_elements,
+            target_false_positive_rate,
+            hash_scheme.unwrap_or(1),
+        )
+    }
+}
+
+/// Validate the deserialized parts and assemble them into a [`BloomFilter`].
+///
+/// [`BloomFilter`]: crate::BloomFilter
+fn build<S: Default, E: de::Error>(
+    num_hash_functions: u64,
+    num_bits: u64,
+    bytes: Vec<u8>,
+    seed: u64,
+    expected_elements: Option<u64>,
+    target_false_positive_rate: Option<f64>,
+    hash_scheme: u8,
+) -> Result<BloomFilter<S>, E> {
+    if hash_scheme & !ENHANCED_DOUBLE_HASHING != HASH_SCHEME_VERSION {
+        return Err(E::invalid_value(
+            de::Unexpected::Unsigned(hash_scheme as u64),
+            &"the hash scheme of this build",
+        ));
+    }
+    let m = usize::try_from(num_bits).map_err(|_| {
+        E::invalid_value(
+            de::Unexpected::Unsigned(num_bits),
+            &"a bit length that fits in usize",
+        )
+    })?;
+    if m == 0 {
+        return Err(E::invalid_value(
+            de::Unexpected::Unsigned(0),
+            &"a nonzero bit length",
+        ));
+    }
+    // A filter with no hash functions would contain everything, and lookups into one with
+    // huge numbers of them would never finish
+    if num_hash_functions == 0 || num_hash_functions > MAX_NUM_HASH_FUNCTIONS as u64 {
+        return Err(E::invalid_value(
+            de::Unexpected::Unsigned(num_hash_functions),
+            &"a number of hash functions in 1..=MAX_NUM_HASH_FUNCTIONS",
+        ));
+    }
+    if bytes.len() != m.div_ceil(8) {
+        return Err(E::invalid_length(bytes.len(), &"ceil(num_bits / 8) bytes"));
+    }
+    if m % 8 != 0 && bytes[bytes.len() - 1] >> (m % 8) != 0 {
+        return Err(E::invalid_value(
+            de::Unexpected::Unsigned(bytes[bytes.len() - 1] as u64),
+            &"a last byte whose bits past num_bits are zero",
+        ));
+    }
+
+    let (derived_elements, derived_rate) = calc_design(m, num_hash_functions as usize);
+    let expected_elements = match expected_elements {
+        Some(n) => usize::try_from(n).map_err(|_| {
+            E::invalid_value(
+                de::Unexpected::Unsigned(n),
+                &"a number of elements that fits in usize",
+            )
+        })?,
+        None => derived_elements,
+    };
+    let target_false_positive_rate = target_false_positive_rate.unwrap_or(derived_rate);
+    if !(0.0..1.0).contains(&target_false_positive_rate) {
+        return Err(E::invalid_value(
+            de::Unexpected::Float(target_false_positive_rate),
+            &"a false positive rate in [0, 1)",
+        ));
+    }
+
+    let mut bits = BitVec::<u8, Lsb0>::from_vec(bytes);
+    bits.truncate(m);
+    let mut bloom = BloomFilter {
+        num_hash_functions: num_hash_functions as usize,
+        bits,
+        seed,
+        expected_elements,
+        target_false_positive_rate,
+        inserted: 0,
+        enhanced_double_hashing: hash_scheme & ENHANCED_DOUBLE_HASHING != 0,
+        hash_builder: S::default(),
+        metrics: Default::default(),
+    };
+    bloom.estimate_inserted();
+    Ok(bloom)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        BloomFilter, ENHANCED_DOUBLE_HASHING, HASH_SCHEME_VERSION, MAX_NUM_HASH_FUNCTIONS,
+    };
+    use serde::de::value::{Error, F64Deserializer, SeqDeserializer, U64Deserializer};
+    use serde::de::{Deserialize, IntoDeserializer};
+
+    /// A field value as it would be handed to the visitor by a self-describing format.
+    enum Value {
+        U64(u64),
+        F64(f64),
+        Bytes(Vec<u8>),
+    }
+
+    impl<'de> IntoDeserializer<'de, Error> for Value {
+        type Deserializer = ValueDeserializer;
+
+        fn into_deserializer(self) -> ValueDeserializer {
+            ValueDeserializer(self)
+        }
+    }
+
+    struct ValueDeserializer(Value);
+
+    impl<'de> serde::Deserializer<'de> for ValueDeserializer {
+        type Error = Error;
+
+        fn deserialize_any<V: serde::de::Visitor<'de>>(
+            self,
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            match self.0 {
+                Value::U64(v) => U64Deserializer::<Error>::new(v).deserialize_any(visitor),
+                Value::F64(v) => F64Deserializer::<Error>::new(v).deserialize_any(visitor),
+                Value::Bytes(v) => SeqDeserializer::new(v.into_iter()).deserialize_any(visitor),
+            }
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map struct enum identifier ignored_any
+        }
+    }
+
+    fn from_parts(k: u64, m: u64, bytes: Vec<u8>) -> Result<BloomFilter, Error> {
+        let values = vec![Value::U64(k), Value::U64(m), Value::Bytes(bytes)];
+        BloomFilter::deserialize(SeqDeserializer::new(values.into_iter()))
+    }
+
+    #[test]
+    fn round_trip_formats() {
+        let mut bloom = BloomFilter::builder()
+            .expected_items(1_000)
+            .seed(42)
+            .enhanced_double_hashing(true)
+            .build()
+            .unwrap();
+        let items: Vec<String> = (0..1_000).map(|i| i.to_string()).collect();
+        for item in &items {
+            bloom.insert(item);
+        }
+
+        let json: BloomFilter =
+            serde_json::from_str(&serde_json::to_string(&bloom).unwrap()).unwrap();
+        let bincode: BloomFilter =
+            bincode::deserialize(&bincode::serialize(&bloom).unwrap()).unwrap();
+        for restored in [json, bincode] {
+            assert_eq!(restored, bloom);
+            assert!(items.iter().all(|item| restored.contains(item)));
+            for i in 0..10_000 {
+                assert_eq!(restored.contains(&i), bloom.contains(&i));
+            }
+        }
+    }
+
+    #[test]
+    fn reject_mismatched_length_json() {
+        // 100 bits take 13 bytes
+        let json = |num_bits: u64, num_bytes: usize| {
+            let bits = serde_json::to_string(&vec![0_u8; num_bytes]).unwrap();
+            serde_json::from_str::<BloomFilter>(&format!(
+                r#"{{"num_hash_functions": 3, "num_bits": {}, "bits": {}}}"#,
+                num_bits, bits
+            ))
+        };
+        assert!(json(100, 13).is_ok());
+        assert!(json(100, 12).is_err());
+        assert!(json(100, 14).is_err());
+        assert!(json(200, 13).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut bloom = BloomFilter::new(1_000, 0.01);
+        let items: Vec<String> = (0..1_000).map(|i| i.to_string()).collect();
+        for item in &items {
+            bloom.insert(item);
+        }
+
+        let restored = from_parts(
+            bloom.num_hash_functions as u64,
+            bloom.bits.len() as u64,
+            bloom.bits.as_raw_slice().to_vec(),
+        )
+        .unwrap();
+        for item in &items {
+            assert!(restored.contains(item));
+        }
+        assert_eq!(restored.bits, bloom.bits);
+    }
+
+    #[test]
+    fn round_trip_seeded() {
+        let mut bloom = BloomFilter::new_with_seed(1_000, 0.01, 42);
+        bloom.insert(&"hi");
+
+        let values = vec![
+            Value::U64(bloom.num_hash_functions as u64),
+            Value::U64(bloom.bits.len() as u64),
+            Value::Bytes(bloom.bits.as_raw_slice().to_vec()),
+            Value::U64(42),
+        ];
+        let restored =
+            BloomFilter::deserialize(SeqDeserializer::<_, Error>::new(values.into_iter())).unwrap();
+        assert_eq!(restored.seed(), 42);
+        assert!(restored.contains(&"hi"));
+        assert_eq!(restored, bloom);
+    }
+
+    #[test]
+    fn round_trip_design() {
+        let bloom = BloomFilter::new(1_000, 0.01);
+        let parts = |design: Vec<Value>| -> Result<BloomFilter, Error> {
+            let mut values = vec![
+                Value::U64(bloom.num_hash_functions as u64),
+                Value::U64(bloom.bits.len() as u64),
+                Value::Bytes(bloom.bits.as_raw_slice().to_vec()),
+                Value::U64(0),
+            ];
+            values.extend(design);
+            BloomFilter::deserialize(SeqDeserializer::<_, Error>::new(values.into_iter()))
+        };
+
+        let restored = parts(vec![Value::U64(1_000), Value::F64(0.01)]).unwrap();
+        assert_eq!(restored.expected_elements(), 1_000);
+        assert_eq!(restored.target_false_positive_rate(), 0.01);
+
+        // Filters serialized before the design was recorded get the derived one
+        let restored = parts(vec![]).unwrap();
+        let (n, f) = crate::calc_design(bloom.num_bits(), bloom.num_hash_functions());
+        assert_eq!(restored.expected_elements(), n);
+        assert_eq!(restored.target_false_positive_rate(), f);
+
+        assert!(parts(vec![Value::U64(1_000), Value::F64(1.5)]).is_err());
+        assert!(parts(vec![Value::U64(1_000)]).is_err());
+    }
+
+    #[test]
+    fn hash_scheme() {
+        let bloom = BloomFilter::new(1_000, 0.01);
+        let parts = |hash_scheme: u64| -> Result<BloomFilter, Error> {
+            let values = vec![
+                Value::U64(bloom.num_hash_functions as u64),
+                Value::U64(bloom.bits.len() as u64),
+                Value::Bytes(bloom.bits.as_raw_slice().to_vec()),
+                Value::U64(0),
+                Value::U64(1_000),
+                Value::F64(0.01),
+                Value::U64(hash_scheme),
+            ];
+            BloomFilter::deserialize(SeqDeserializer::<_, Error>::new(values.into_iter()))
+        };
+
+        assert_eq!(parts(HASH_SCHEME_VERSION as u64).unwrap(), bloom);
+        assert!(parts(HASH_SCHEME_VERSION as u64 + 1).is_err());
+        let enhanced = parts((HASH_SCHEME_VERSION | ENHANCED_DOUBLE_HASHING) as u64).unwrap();
+        assert!(enhanced.enhanced_double_hashing());
+        assert!(parts(((HASH_SCHEME_VERSION + 1) | ENHANCED_DOUBLE_HASHING) as u64).is_err());
+    }
+
+    #[test]
+    fn reject_mismatched_length() {
+        assert!(from_parts(3, 100, vec![0; 12]).is_err());
+        assert!(from_parts(3, 100, vec![0; 14]).is_err());
+        assert!(from_parts(3, 100, vec![0; 13]).is_ok());
+    }
+
+    #[test]
+    fn reject_nonzero_padding() {
+        let mut bytes = vec![0; 13];
+        bytes[12] = 0b1111;
+        assert!(from_parts(3, 100, bytes.clone()).is_ok());
+        bytes[12] = 0b1_0000;
+        assert!(from_parts(3, 100, bytes).is_err());
+    }
+
+    #[test]
+    fn reject_zero_bits() {
+        assert!(from_parts(3, 0, vec![]).is_err());
+    }
+
+    #[test]
+    fn reject_zero_hash_functions() {
+        assert!(from_parts(0, 100, vec![0; 13]).is_err());
+    }
+
+    #[test]
+    fn reject_too_many_hash_functions() {
+        let max = MAX_NUM_HASH_FUNCTIONS as u64;
+        assert!(from_parts(max, 100, vec![0; 13]).is_ok());
+        assert!(from_parts(max + 1, 100, vec![0; 13]).is_err());
+        assert!(from_parts(u64::MAX, 100, vec![0; 13]).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! A Bloom filter whose items age out, for deduplicating over a sliding window.
+
+use crate::{BloomFilter, DefaultHashBuilder};
+use core::hash::{BuildHasher, Hash};
+use std::time::{Duration, Instant};
+
+/// Bloom filter whose items expire, approximating a time to live without per-item
+/// timestamps.
+///
+/// It keeps two generations of [`BloomFilter`]s: items are inserted into the current one,
+/// and looked up in both. [`TimedBloomFilter::rotate`] discards the previous generation and
+/// starts a new, empty current one, so an item is forgotten after two rotations. A filter
+/// built with [`TimedBloomFilter::with_window`] also rotates on its own once a generation is
+/// `window` old, so an item lives for between one and two windows.
+///
+/// Each generation is sized for `num_elements` items at `false_positive_rate`, and since
+/// lookups check both, the filter's false positive rate is up to about twice that.
+///
+/// [`BloomFilter`]: crate::BloomFilter
+#[derive(Debug, Clone)]
+pub struct TimedBloomFilter<S = DefaultHashBuilder> {
+    // Generation items are inserted into, and the one before it
+    current: BloomFilter<S>,
+    previous: BloomFilter<S>,
+
+    // How long a generation stays current before rotating on its own, if at all
+    window: Option<Duration>,
+
+    // When the current generation started
+    rotated_at: Instant,
+}
+
+impl TimedBloomFilter {
+    /// Instantiate a new [`TimedBloomFilter`] whose generations are each sized for
+    /// `num_elements` at `false_positive_rate`, like [`BloomFilter::new`], and which only
+    /// rotates when [`TimedBloomFilter::rotate`] is called.
+    ///
+    /// [`TimedBloomFilter`]: TimedBloomFilter
+    /// [`BloomFilter::new`]: crate::BloomFilter::new
+    pub fn new(num_elements: usize, false_positive_rate: f32) -> TimedBloomFilter {
+        TimedBloomFilter::with_hasher(
+            num_elements,
+            false_positive_rate,
+            None,
+            DefaultHashBuilder::default(),
+        )
+    }
+
+    /// Instantiate a new [`TimedBloomFilter`] like [`TimedBloomFilter::new`], which also
+    /// rotates on its own every `window`, so that items expire between one and two windows
+    /// after they were last inserted.
+    ///
+    /// [`TimedBloomFilter`]: TimedBloomFilter
+    pub fn with_window(
+        num_elements: usize,
+        false_positive_rate: f32,
+        window: Duration,
+    ) -> TimedBloomFilter {
+        TimedBloomFilter::with_hasher(
+            num_elements,
+            false_positive_rate,
+            Some(window),
+            DefaultHashBuilder::default(),
+        )
+    }
+}
+
+impl<S: BuildHasher + Clone> TimedBloomFilter<S>
+where
+    S::Hasher: 'static,
+{
+    /// Instantiate a new [`TimedBloomFilter`] like [`TimedBloomFilter::new`], or
+    /// [`TimedBloomFilter::with_window`] if `window` is given, hashing items with hashers
+    /// built by `hash_builder`.
+    ///
+    /// [`TimedBloomFilter`]: TimedBloomFilter
+    pub fn with_hasher(
+        num_elements: usize,
+        false_positive_rate: f32,
+        window: Option<Duration>,
+        hash_builder: S,
+    ) -> TimedBloomFilter<S> {
+        let current =
+            BloomFilter::with_hasher(num_elements, false_positive_rate, hash_builder.clone());
+        let previous = BloomFilter::with_hasher(num_elements, false_positive_rate, hash_builder);
+        TimedBloomFilter {
+            current,
+            previous,
+            window,
+            rotated_at: Instant::now(),
+        }
+    }
+}
+
+impl<S: BuildHasher> TimedBloomFilter<S>
+where
+    S::Hasher: 'static,
+{
+    /// Returns how long a generation stays current before the filter rotates on its own, or
+    /// `None` if it only rotates when [`TimedBloomFilter::rotate`] is called.
+    pub fn window(&self) -> Option<Duration> {
+        self.window
+    }
+
+    /// Returns the number of items each generation was designed to hold.
+    pub fn capacity(&self) -> usize {
+        self.current.capacity()
+    }
+
+    /// Returns the current generation, which items are inserted into.
+    pub fn current(&self) -> &BloomFilter<S> {
+        &self.current
+    }
+
+    /// Returns the previous generation, which is discarded at the next rotation.
+    pub fn previous(&self) -> &BloomFilter<S> {
+        &self.previous
+    }
+
+    /// Insert an item into the current generation, first rotating if its window has elapsed.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        self.rotate_expired(Instant::now());
+        self.current.insert(item);
+    }
+
+    /// Returns whether either generation contains the item, ignoring generations whose
+    /// windows have elapsed even if the filter hasn't rotated since. It may return a false
+    /// positive but will never return a false negative for an item that hasn't expired.
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        self.contains_at(item, Instant::now())
+    }
+
+    /// Discard the previous generation and make the current one the previous, starting an
+    /// empty current generation. The discarded generation's allocation is reused.
+    pub fn rotate(&mut self) {
+        self.rotate_at(Instant::now());
+    }
+
+    /// Rotate, the new current generation starting at `now`.
+    fn rotate_at(&mut self, now: Instant) {
+        core::mem::swap(&mut self.current, &mut self.previous);
+        self.current.clear();
+        self.rotated_at = now;
+    }
+
+    /// Rotate once for each window elapsed since the last rotation as of `now`, at most
+    /// twice since that discards every item.
+    ///
+    /// Generations keep to window boundaries however late the rotation, so that items
+    /// expire within two windows of being inserted: a single rotation starts the new
+    /// generation a window after the last one, not at `now`.
+    fn rotate_expired(&mut self, now: Instant) {
+        match self.expired_generations(now) {
+            0 => {}
+            1 => {
+                let window = self.window.expect("only filters with a window expire");
+                self.rotate_at(self.rotated_at + window);
+            }
+            _ => {
+                self.current.clear();
+                self.previous.clear();
+                self.rotated_at = now;
+            }
+        }
+    }
+
+    /// Returns whether the item is in a generation that hasn't expired as of `now`.
+    fn contains_at<T: Hash + ?Sized>(&self, item: &T, now: Instant) -> bool {
+        match self.expired_generations(now) {
+            0 => self.current.contains(item) || self.previous.contains(item),
+            1 => self.current.contains(item),
+            _ => false,
+        }
+    }
+
+    /// Returns the number of rotations due as of `now`, at most 2.
+    fn expired_generations(&self, now: Instant) -> u32 {
+        let Some(window) = self.window else {
+            return 0;
+        };
+        let elapsed = now.saturating_duration_since(self.rotated_at);
+        if elapsed >= window.saturating_mul(2) {
+            2
+        } else if elapsed >= window {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timed::*;
+
+    #[test]
+    fn expires_after_two_rotations() {
+        let mut bloom = TimedBloomFilter::new(1_000, 0.01);
+        bloom.insert(&"old");
+        bloom.rotate();
+        bloom.insert(&"new");
+        assert!(bloom.contains(&"old"));
+        assert!(bloom.contains(&"new"));
+
+        bloom.rotate();
+        assert!(!bloom.contains(&"old"));
+        assert!(bloom.contains(&"new"));
+        assert!(bloom.current().is_empty());
+        assert_eq!(bloom.window(), None);
+    }
+
+    #[test]
+    fn window() {
+        let window = Duration::from_secs(60);
+        let mut bloom = TimedBloomFilter::with_window(1_000, 0.01, window);
+        assert_eq!(bloom.window(), Some(window));
+        assert_eq!(bloom.capacity(), 1_000);
+        bloom.insert(&"old");
+        let start = bloom.rotated_at;
+
+        // Items outlive one window but not two, even before the filter rotates
+        assert!(bloom.contains_at(&"old", start + window));
+        assert!(!bloom.contains_at(&"old", start + window * 2));
+
+        bloom.rotate_expired(start + window);
+        assert!(bloom.current().is_empty());
+        assert!(bloom.previous().contains(&"old"));
+        bloom.current.insert(&"new");
+        bloom.rotate_expired(start + window * 3);
+        assert!(bloom.current().is_empty());
+        assert!(bloom.previous().is_empty());
+    }
+
+    #[test]
+    fn late_rotation() {
+        let window = Duration::from_secs(60);
+        let mut bloom = TimedBloomFilter::with_window(1_000, 0.01, window);
+        bloom.insert(&"old");
+        let start = bloom.rotated_at;
+
+        // Nothing happens until nearly two windows later, which rotates once, as of the end
+        // of the first window rather than now
+        bloom.rotate_expired(start + window.mul_f64(1.9));
+        assert_eq!(bloom.rotated_at, start + window);
+        assert!(bloom.previous().contains(&"old"));
+        bloom.current.insert(&"new");
+
+        // So the old item still expires two windows after it was inserted
+        assert!(bloom.contains_at(&"old", start + window.mul_f64(1.95)));
+        assert!(!bloom.contains_at(&"old", start + window * 2));
+        assert!(bloom.contains_at(&"new", start + window * 2));
+    }
+}
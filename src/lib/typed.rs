@@ -0,0 +1,156 @@
+//! A [`BloomFilter`] restricted to items of a single type.
+//!
+//! [`BloomFilter`]: crate::BloomFilter
+
+use crate::{BloomFilter, DefaultHashBuilder};
+use bitvec::mem::BitRegister;
+use bitvec::store::BitStore;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+
+/// Bloom filter whose items are all of type `T`.
+///
+/// A plain [`BloomFilter`] accepts items of any type, and values of different types may
+/// hash alike, e.g. `1u8` and `true`, or not, e.g. `1u8` and `1u32`, so querying with the
+/// wrong type silently yields wrong answers. This wrapper only accepts `T`:
+///
+/// ```compile_fail
+/// use bloom::TypedBloomFilter;
+/// let mut bloom: TypedBloomFilter<u64> = TypedBloomFilter::new(100, 0.01);
+/// bloom.insert(&1u32);
+/// ```
+///
+/// Otherwise it behaves like the [`BloomFilter`] it wraps:
+///
+/// ```
+/// use bloom::TypedBloomFilter;
+/// let mut bloom: TypedBloomFilter<str> = TypedBloomFilter::new(100, 0.01);
+/// bloom.insert("hi");
+/// assert!(bloom.contains("hi"));
+/// assert!(!bloom.contains("yo"));
+/// ```
+///
+/// [`BloomFilter`]: crate::BloomFilter
+pub struct TypedBloomFilter<T: ?Sized, S = DefaultHashBuilder, W: BitRegister + BitStore = u8> {
+    inner: BloomFilter<S, W>,
+    // `fn(&T)` so that the filter is `Send`, `Sync` and covariant regardless of `T`, as it
+    // stores no `T`
+    _item: PhantomData<fn(&T)>,
+}
+
+impl<T: Hash + ?Sized> TypedBloomFilter<T> {
+    /// Instantiate a new [`TypedBloomFilter`] sized for `num_elements` at
+    /// `false_positive_rate`, like [`BloomFilter::new`].
+    ///
+    /// [`TypedBloomFilter`]: TypedBloomFilter
+    #[cfg(feature = "std")]
+    pub fn new(num_elements: usize, false_positive_rate: f32) -> TypedBloomFilter<T> {
+        BloomFilter::new(num_elements, false_positive_rate).into()
+    }
+}
+
+impl<T: Hash + ?Sized, S: BuildHasher, W: BitRegister + BitStore> TypedBloomFilter<T, S, W>
+where
+    S::Hasher: 'static,
+{
+    /// Insert an item into the Bloom filter.
+    pub fn insert(&mut self, item: &T) {
+        self.inner.insert(item);
+    }
+
+    /// Returns whether the Bloom filter contains an item. It may return a false positive but
+    /// will never return a false negative.
+    pub fn contains(&self, item: &T) -> bool {
+        self.inner.contains(item)
+    }
+
+    /// Reset the Bloom filter to its empty state.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    /// Returns the underlying [`BloomFilter`], e.g. to serialize it or estimate its count.
+    ///
+    /// [`BloomFilter`]: crate::BloomFilter
+    pub fn as_inner(&self) -> &BloomFilter<S, W> {
+        &self.inner
+    }
+
+    /// Returns the underlying [`BloomFilter`], which accepts items of any type.
+    ///
+    /// [`BloomFilter`]: crate::BloomFilter
+    pub fn into_inner(self) -> BloomFilter<S, W> {
+        self.inner
+    }
+}
+
+/// Wraps a [`BloomFilter`], whose items must all have been of type `T` for lookups to be
+/// meaningful.
+///
+/// [`BloomFilter`]: crate::BloomFilter
+impl<T: ?Sized, S, W: BitRegister + BitStore> From<BloomFilter<S, W>>
+    for TypedBloomFilter<T, S, W>
+{
+    fn from(inner: BloomFilter<S, W>) -> Self {
+        TypedBloomFilter {
+            inner,
+            _item: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized, S: Clone, W: BitRegister + BitStore> Clone for TypedBloomFilter<T, S, W> {
+    fn clone(&self) -> Self {
+        self.inner.clone().into()
+    }
+}
+
+impl<T: ?Sized, S, W: BitRegister + BitStore> PartialEq for TypedBloomFilter<T, S, W> {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl<T: ?Sized, S, W: BitRegister + BitStore> Eq for TypedBloomFilter<T, S, W> {}
+
+impl<T: ?Sized, S, W: BitRegister + BitStore> fmt::Debug for TypedBloomFilter<T, S, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TypedBloomFilter")
+            .field(&self.inner)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::typed::*;
+
+    #[test]
+    fn matches_untyped() {
+        let mut typed: TypedBloomFilter<u64> = TypedBloomFilter::new(1_000, 0.01);
+        let mut untyped = BloomFilter::new(1_000, 0.01);
+        for i in 0..500u64 {
+            typed.insert(&i);
+            untyped.insert(&i);
+        }
+        assert_eq!(typed.as_inner(), &untyped);
+        for i in 0..2_000u64 {
+            assert_eq!(typed.contains(&i), untyped.contains(&i));
+        }
+
+        let copy = typed.clone();
+        typed.clear();
+        assert!(!typed.contains(&1));
+        assert!(copy.contains(&1));
+        assert_eq!(copy.into_inner(), untyped);
+    }
+
+    #[test]
+    fn unsized_items() {
+        let mut bloom: TypedBloomFilter<str> = BloomFilter::new(1_000, 0.01).into();
+        bloom.insert("hi");
+        assert!(bloom.contains("hi"));
+        assert!(!bloom.contains("yo"));
+    }
+}
@@ -0,0 +1,121 @@
+//! A byte-oriented wrapper around [`BloomFilter`] for calling across a WebAssembly boundary,
+//! enabled by the `wasm` feature.
+//!
+//! [`BloomFilter`]: crate::BloomFilter
+
+use crate::{BloomError, BloomFilter};
+use alloc::vec::Vec;
+
+/// Bloom filter with an API restricted to the types JavaScript bindings exchange directly:
+/// numbers, booleans and byte arrays.
+///
+/// Items are byte strings, inserted and queried as with [`BloomFilter::insert_bytes`] and
+/// [`BloomFilter::contains_bytes`], so strings should be passed as their UTF-8 encoding. The
+/// serialized form is the one of [`BloomFilter::to_bytes`], so filters can be built on a
+/// server and shipped to a browser, or the other way around.
+///
+/// Its methods map one to one onto `wasm-bindgen` exports, e.g. in a newtype marked
+/// `#[wasm_bindgen]` forwarding to them.
+///
+/// [`BloomFilter`]: crate::BloomFilter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmBloomFilter {
+    inner: BloomFilter,
+}
+
+impl WasmBloomFilter {
+    /// Instantiate a new [`WasmBloomFilter`] sized for `num_elements` at
+    /// `false_positive_rate`, like [`BloomFilter::new`].
+    ///
+    /// Returns [`BloomError::InvalidParameter`] if `false_positive_rate` isn't in `(0, 1)`.
+    ///
+    /// [`WasmBloomFilter`]: WasmBloomFilter
+    pub fn new(num_elements: u32, false_positive_rate: f64) -> Result<WasmBloomFilter, BloomError> {
+        let inner = BloomFilter::builder()
+            .expected_items(num_elements as usize)
+            .false_positive_rate(false_positive_rate)
+            .build()?;
+        Ok(WasmBloomFilter { inner })
+    }
+
+    /// Deserialize a filter written by [`WasmBloomFilter::to_bytes`] or
+    /// [`BloomFilter::to_bytes`], returning the same errors as [`BloomFilter::from_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmBloomFilter, BloomError> {
+        Ok(WasmBloomFilter {
+            inner: BloomFilter::from_bytes(bytes)?,
+        })
+    }
+
+    /// Serialize the filter like [`BloomFilter::to_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.inner.to_bytes()
+    }
+
+    /// Insert a byte string into the filter.
+    pub fn insert_bytes(&mut self, bytes: &[u8]) {
+        self.inner.insert_bytes(bytes);
+    }
+
+    /// Returns whether the filter contains a byte string. It may return a false positive but
+    /// will never return a false negative.
+    pub fn contains_bytes(&self, bytes: &[u8]) -> bool {
+        self.inner.contains_bytes(bytes)
+    }
+
+    /// Reset the filter to its empty state.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    /// Returns the underlying [`BloomFilter`].
+    ///
+    /// [`BloomFilter`]: crate::BloomFilter
+    pub fn into_inner(self) -> BloomFilter {
+        self.inner
+    }
+}
+
+/// Wraps a [`BloomFilter`], whose items must have been inserted as byte strings to be found.
+///
+/// [`BloomFilter`]: crate::BloomFilter
+impl From<BloomFilter> for WasmBloomFilter {
+    fn from(inner: BloomFilter) -> Self {
+        WasmBloomFilter { inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wasm::*;
+
+    #[test]
+    fn byte_api() {
+        let mut bloom = WasmBloomFilter::new(1_000, 0.01).unwrap();
+        bloom.insert_bytes(b"hi");
+        assert!(bloom.contains_bytes(b"hi"));
+        assert!(!bloom.contains_bytes(b"yo"));
+
+        let bytes = bloom.to_bytes();
+        let restored = WasmBloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(restored, bloom);
+        assert!(restored.contains_bytes(b"hi"));
+        assert!(restored.into_inner().contains_bytes(b"hi"));
+
+        bloom.clear();
+        assert!(!bloom.contains_bytes(b"hi"));
+    }
+
+    #[test]
+    fn errors() {
+        for rate in [0.0, 1.0, f64::NAN] {
+            assert!(matches!(
+                WasmBloomFilter::new(1_000, rate),
+                Err(BloomError::InvalidParameter(_))
+            ));
+        }
+        assert_eq!(
+            WasmBloomFilter::from_bytes(b"BLMF"),
+            Err(BloomError::UnexpectedEof)
+        );
+    }
+}
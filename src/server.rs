@@ -1,55 +1,940 @@
-use bloom::BloomFilter;
+mod config;
+mod hasher;
+
+use bloom::{AtomicBloomFilter, BloomError, BloomFilter};
 use bloomd::bloomd_server::{Bloomd, BloomdServer};
-use bloomd::{ContainsRequest, ContainsResponse, InsertRequest, InsertResponse};
+use bloomd::{
+    BatchInsertRequest, BatchInsertResponse, ClearRequest, ClearResponse, ContainsRequest,
+    ContainsResponse, CreateFilterRequest, CreateFilterResponse, InsertRequest, InsertResponse,
+    StatsRequest, StatsResponse, WatchStatsRequest,
+};
+use config::{Config, ConfigError, USAGE};
+use hasher::ItemHasher;
 use parking_lot::RwLock;
+use std::collections::hash_map::{Entry, HashMap};
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fs, io};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{transport::Server, Request, Response, Status};
 
+/// The default filter, used by requests that don't name one.
+const DEFAULT_FILTER: &str = "";
+
+// Start of persisted filters, followed by the id of the hasher they were hashed with. Files
+// from before the hasher could be picked have no header and were hashed with SipHash.
+const PERSIST_MAGIC: &[u8; 4] = b"BLMD";
+
+/// A filter served by bloomd, hashing items with the hasher picked at startup.
+type Filter = AtomicBloomFilter<ItemHasher>;
+
 #[derive(Debug)]
 pub struct BloomdService {
-    bloom_filter: RwLock<BloomFilter>,
+    // Filters by name. Inserts only take the read lock, since the filters' bits are atomic;
+    // the write lock is only needed to add filters. Shared with the tasks streaming stats.
+    filters: Arc<RwLock<HashMap<String, Filter>>>,
+
+    // Sizing of the filters that inserts create on demand
+    num_elements: usize,
+    false_positive_rate: f32,
+
+    // Hasher of every filter
+    hasher: ItemHasher,
+
+    // Whether inserts into saturated filters fail rather than succeed
+    reject_when_saturated: bool,
+
+    // Longest item accepted, in bytes, if limited
+    max_item_bytes: Option<usize>,
+
+    // Largest filter clients may create, in bytes
+    max_filter_bytes: usize,
 }
 
 pub mod bloomd {
     tonic::include_proto!("bloomd");
 }
 
+impl BloomdService {
+    /// Serve `filters` as configured by `config`, making sure the default filter exists.
+    /// The filters must hash items with `config.hasher`.
+    fn new(mut filters: HashMap<String, Filter>, config: &Config) -> BloomdService {
+        filters
+            .entry(DEFAULT_FILTER.to_string())
+            .or_insert_with(|| {
+                Filter::with_hasher(
+                    config.num_elements,
+                    config.false_positive_rate,
+                    config.hasher,
+                )
+            });
+        BloomdService {
+            filters: Arc::new(RwLock::new(filters)),
+            num_elements: config.num_elements,
+            false_positive_rate: config.false_positive_rate,
+            hasher: config.hasher,
+            reject_when_saturated: config.reject_when_saturated,
+            max_item_bytes: config.max_item_bytes,
+            max_filter_bytes: config.max_filter_bytes,
+        }
+    }
+
+    /// Returns an `INVALID_ARGUMENT` status if any of `items` is longer than the server accepts.
+    fn check_item_len<'a>(&self, items: impl IntoIterator<Item = &'a String>) -> Option<Status> {
+        let max = self.max_item_bytes?;
+        items.into_iter().find(|item| item.len() > max).map(|item| {
+            Status::invalid_argument(format!(
+                "item is {} bytes long, longer than the maximum of {}",
+                item.len(),
+                max
+            ))
+        })
+    }
+
+    /// Run `f` on the filter named `name`, creating it first if it doesn't exist.
+    fn with_filter<T>(&self, name: &str, f: impl FnOnce(&Filter) -> T) -> T {
+        if let Some(bf) = self.filters.read().get(name) {
+            return f(bf);
+        }
+
+        let mut filters = self.filters.write();
+        let bf = filters.entry(name.to_string()).or_insert_with(|| {
+            Filter::with_hasher(self.num_elements, self.false_positive_rate, self.hasher)
+        });
+        f(bf)
+    }
+
+    /// Run `insert` on the filter named `name` like [`BloomdService::with_filter`], unless
+    /// inserts into saturated filters are rejected and it's saturated. Returns the
+    /// `RESOURCE_EXHAUSTED` status to fail with in that case.
+    fn insert_into(&self, name: &str, insert: impl FnOnce(&Filter)) -> Option<Status> {
+        self.with_filter(name, |bf| {
+            if self.reject_when_saturated && bf.is_saturated() {
+                return Some(Status::resource_exhausted(format!(
+                    "filter {:?} holds more than the {} items it was sized for",
+                    name,
+                    bf.capacity()
+                )));
+            }
+            insert(bf);
+            None
+        })
+    }
+}
+
+/// Snapshot the stats reported for `bf`.
+fn filter_stats(bf: &Filter) -> StatsResponse {
+    StatsResponse {
+        fill_ratio: bf.fill_ratio(),
+        estimated_count: bf.estimate_count() as u64,
+        false_positive_rate: bf.current_false_positive_rate(),
+        size_bytes: bf.size() as u64,
+    }
+}
+
 #[tonic::async_trait]
 impl Bloomd for BloomdService {
+    async fn create_filter(
+        &self,
+        req: Request<CreateFilterRequest>,
+    ) -> Result<Response<CreateFilterResponse>, Status> {
+        println!("Got a request: {:?}", req);
+
+        let req = req.get_ref();
+        let num_elements = usize::try_from(req.num_elements)
+            .map_err(|_| Status::invalid_argument("num_elements is too large"))?;
+        // Filters are sized from an `f32` rate, so rates too small for one are rejected too
+        let false_positive_rate = req.false_positive_rate as f32;
+        if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+            return Err(Status::invalid_argument(format!(
+                "false_positive_rate must be in (0, 1), got {}",
+                req.false_positive_rate
+            )));
+        }
+        let (num_bits, _) = bloom::optimal_params(num_elements, false_positive_rate as f64);
+        if num_bits.div_ceil(8) > self.max_filter_bytes {
+            return Err(Status::invalid_argument(format!(
+                "a filter for {} elements at false positive rate {} takes {} bytes, more than \
+                 the maximum of {}",
+                num_elements,
+                req.false_positive_rate,
+                num_bits.div_ceil(8),
+                self.max_filter_bytes
+            )));
+        }
+
+        match self.filters.write().entry(req.filter_name.clone()) {
+            Entry::Occupied(_) => Err(Status::already_exists(format!(
+                "filter {:?} already exists",
+                req.filter_name
+            ))),
+            Entry::Vacant(entry) => {
+                entry.insert(Filter::with_hasher(
+                    num_elements,
+                    false_positive_rate,
+                    self.hasher,
+                ));
+                Ok(Response::new(bloomd::CreateFilterResponse {}))
+            }
+        }
+    }
+
     async fn insert(
         &self,
         req: Request<InsertRequest>,
     ) -> Result<Response<InsertResponse>, Status> {
         println!("Got a request: {:?}", req);
 
-        self.bloom_filter.write().insert(&req.get_ref().item);
+        let req = req.get_ref();
+        if let Some(status) = self.check_item_len([&req.item]) {
+            return Err(status);
+        }
+        if let Some(status) = self.insert_into(&req.filter_name, |bf| bf.insert(&req.item)) {
+            return Err(status);
+        }
         Ok(Response::new(bloomd::InsertResponse {}))
     }
 
+    async fn batch_insert(
+        &self,
+        req: Request<BatchInsertRequest>,
+    ) -> Result<Response<BatchInsertResponse>, Status> {
+        println!("Got a request: {:?}", req);
+
+        let req = req.get_ref();
+        if let Some(status) = self.check_item_len(&req.items) {
+            return Err(status);
+        }
+        if let Some(status) = self.insert_into(&req.filter_name, |bf| bf.insert_all(&req.items)) {
+            return Err(status);
+        }
+        Ok(Response::new(bloomd::BatchInsertResponse {
+            num_items: req.items.len() as u64,
+        }))
+    }
+
     async fn contains(
         &self,
         req: Request<ContainsRequest>,
     ) -> Result<Response<ContainsResponse>, Status> {
         println!("Got a request: {:?}", req);
 
-        Ok(Response::new(bloomd::ContainsResponse {
-            contains_item: self.bloom_filter.read().contains(&req.get_ref().item),
-        }))
+        let req = req.get_ref();
+        if let Some(status) = self.check_item_len([&req.item]) {
+            return Err(status);
+        }
+        let contains_item = self
+            .filters
+            .read()
+            .get(&req.filter_name)
+            .map_or(false, |bf| bf.contains(&req.item));
+        Ok(Response::new(bloomd::ContainsResponse { contains_item }))
+    }
+
+    async fn clear(&self, req: Request<ClearRequest>) -> Result<Response<ClearResponse>, Status> {
+        println!("Got a request: {:?}", req);
+
+        if let Some(bf) = self.filters.read().get(&req.get_ref().filter_name) {
+            bf.clear();
+        }
+        Ok(Response::new(bloomd::ClearResponse {}))
+    }
+
+    async fn stats(&self, req: Request<StatsRequest>) -> Result<Response<StatsResponse>, Status> {
+        println!("Got a request: {:?}", req);
+
+        let name = &req.get_ref().filter_name;
+        let filters = self.filters.read();
+        let bf = filters
+            .get(name)
+            .ok_or_else(|| Status::not_found(format!("filter {:?} doesn't exist", name)))?;
+        Ok(Response::new(filter_stats(bf)))
+    }
+
+    type WatchStatsStream = ReceiverStream<Result<StatsResponse, Status>>;
+
+    async fn watch_stats(
+        &self,
+        req: Request<WatchStatsRequest>,
+    ) -> Result<Response<Self::WatchStatsStream>, Status> {
+        println!("Got a request: {:?}", req);
+
+        let WatchStatsRequest {
+            filter_name,
+            interval_ms,
+        } = req.into_inner();
+        if interval_ms == 0 {
+            return Err(Status::invalid_argument("interval_ms must be positive"));
+        }
+        if !self.filters.read().contains_key(&filter_name) {
+            return Err(Status::not_found(format!(
+                "filter {:?} doesn't exist",
+                filter_name
+            )));
+        }
+
+        // Filters are never removed, so the filter outlives the stream. Sending fails once
+        // the client disconnects and drops the receiving end, which ends the task.
+        let filters = self.filters.clone();
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(interval_ms));
+            loop {
+                interval.tick().await;
+                let stats = filter_stats(&filters.read()[&filter_name]);
+                if tx.send(Ok(stats)).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// Load the filters persisted at `config.persist_path`, if any. They must have been hashed
+/// with `config.hasher`, as items hash to different bits under another hasher.
+fn load_filters(config: &Config) -> Result<HashMap<String, Filter>, Box<dyn Error>> {
+    if let Some(path) = &config.persist_path {
+        match fs::read(path) {
+            Ok(bytes) => {
+                let (hasher, filters) = decode_filters(&bytes)?;
+                if hasher != config.hasher {
+                    return Err(format!(
+                        "filters in {} were hashed with --hasher {}, not {}",
+                        path.display(),
+                        hasher,
+                        config.hasher
+                    )
+                    .into());
+                }
+                return Ok(filters);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(HashMap::new())
+}
+
+/// Persist the filters to `path`. They're written to a temporary file first and then renamed,
+/// so a crash mid-write never leaves a truncated file behind.
+fn save_filters(
+    path: &Path,
+    hasher: ItemHasher,
+    filters: &HashMap<String, Filter>,
+) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, encode_filters(hasher, filters))?;
+    fs::rename(&tmp, path)
+}
+
+/// Encode the filters, hashed with `hasher`, as [`PERSIST_MAGIC`] and the hasher's id
+/// followed by a sequence of entries, each the length of the name as a little-endian `u64`,
+/// the name, the length of the filter as a little-endian `u64` and the filter as written by
+/// [`BloomFilter::to_bytes`].
+fn encode_filters(hasher: ItemHasher, filters: &HashMap<String, Filter>) -> Vec<u8> {
+    let mut names: Vec<_> = filters.keys().collect();
+    names.sort();
+
+    let mut bytes = PERSIST_MAGIC.to_vec();
+    bytes.push(hasher.id());
+    for name in names {
+        let filter = filters[name].to_bloom_filter().to_bytes();
+        bytes.extend_from_slice(&(name.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&(filter.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&filter);
+    }
+    bytes
+}
+
+/// Decode filters written by [`encode_filters`], returning them along with the hasher they
+/// were hashed with.
+///
+/// Returns [`BloomError::UnsupportedHashScheme`] if they were hashed with a hasher this
+/// build doesn't know.
+fn decode_filters(mut bytes: &[u8]) -> Result<(ItemHasher, HashMap<String, Filter>), BloomError> {
+    fn take<'a>(bytes: &mut &'a [u8]) -> Result<&'a [u8], BloomError> {
+        if bytes.len() < 8 {
+            return Err(BloomError::UnexpectedEof);
+        }
+        let (len, rest) = bytes.split_at(8);
+        let len = usize::try_from(u64::from_le_bytes(len.try_into().unwrap()))
+            .map_err(|_| BloomError::Corrupt)?;
+        if rest.len() < len {
+            return Err(BloomError::UnexpectedEof);
+        }
+        let (field, rest) = rest.split_at(len);
+        *bytes = rest;
+        Ok(field)
+    }
+
+    let hasher = match bytes.strip_prefix(PERSIST_MAGIC) {
+        Some(rest) => {
+            let (&id, rest) = rest.split_first().ok_or(BloomError::UnexpectedEof)?;
+            bytes = rest;
+            ItemHasher::from_id(id).ok_or(BloomError::UnsupportedHashScheme(id))?
+        }
+        None => ItemHasher::Sip,
+    };
+
+    let mut filters = HashMap::new();
+    while !bytes.is_empty() {
+        let name =
+            String::from_utf8(take(&mut bytes)?.to_vec()).map_err(|_| BloomError::Corrupt)?;
+        let filter = BloomFilter::from_bytes_with_hasher(take(&mut bytes)?, hasher)?;
+        filters.insert(name, filter.into());
+    }
+    Ok((hasher, filters))
+}
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for Ctrl-C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
     }
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Allocate Bloom filter
-    let bf = BloomFilter::new(100_000, 0.01);
-    println!("BloomFilter size={} bytes", bf.size());
+async fn main() -> Result<(), Box<dyn Error>> {
+    let config = match Config::parse(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(ConfigError::Help) => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // Restore the persisted Bloom filters, allocating the default one if needed
+    let service = Arc::new(BloomdService::new(load_filters(&config)?, &config));
+    for (name, bf) in service.filters.read().iter() {
+        println!(
+            "BloomFilter name={:?} num_bits={} num_hash_functions={} size={} bytes",
+            name,
+            bf.num_bits(),
+            bf.num_hash_functions(),
+            bf.size()
+        );
+    }
 
-    let addr = "[::1]:50051".parse()?;
     Server::builder()
-        .add_service(BloomdServer::new(BloomdService {
-            bloom_filter: RwLock::new(bf),
-        }))
-        .serve(addr)
+        .add_service(BloomdServer::from_arc(service.clone()))
+        .serve_with_shutdown(config.bind_addr, shutdown_signal())
         .await?;
 
+    if let Some(path) = &config.persist_path {
+        save_filters(path, service.hasher, &service.filters.read())?;
+        println!("Saved BloomFilters to {}", path.display());
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn service() -> BloomdService {
+        BloomdService::new(HashMap::new(), &Config::default())
+    }
+
+    async fn contains(service: &BloomdService, filter_name: &str, item: &str) -> bool {
+        service
+            .contains(Request::new(ContainsRequest {
+                item: item.to_string(),
+                filter_name: filter_name.to_string(),
+            }))
+            .await
+            .unwrap()
+            .get_ref()
+            .contains_item
+    }
+
+    async fn insert(service: &BloomdService, filter_name: &str, item: &str) {
+        service
+            .insert(Request::new(InsertRequest {
+                item: item.to_string(),
+                filter_name: filter_name.to_string(),
+            }))
+            .await
+            .unwrap();
+    }
+
+    async fn create_filter(
+        service: &BloomdService,
+        filter_name: &str,
+        num_elements: u64,
+        false_positive_rate: f64,
+    ) -> Result<(), Status> {
+        service
+            .create_filter(Request::new(CreateFilterRequest {
+                filter_name: filter_name.to_string(),
+                num_elements,
+                false_positive_rate,
+            }))
+            .await
+            .map(|_| ())
+    }
+
+    async fn stats(service: &BloomdService, filter_name: &str) -> Result<StatsResponse, Status> {
+        service
+            .stats(Request::new(StatsRequest {
+                filter_name: filter_name.to_string(),
+            }))
+            .await
+            .map(Response::into_inner)
+    }
+
+    #[tokio::test]
+    async fn clear() {
+        let service = service();
+        insert(&service, DEFAULT_FILTER, "hi").await;
+        assert!(contains(&service, DEFAULT_FILTER, "hi").await);
+
+        service
+            .clear(Request::new(ClearRequest {
+                filter_name: DEFAULT_FILTER.to_string(),
+            }))
+            .await
+            .unwrap();
+        assert!(!contains(&service, DEFAULT_FILTER, "hi").await);
+    }
+
+    #[tokio::test]
+    async fn stats_default_filter() {
+        let service = service();
+        let items = (0..1_000).map(|i| i.to_string()).collect();
+        service
+            .batch_insert(Request::new(BatchInsertRequest {
+                items,
+                filter_name: DEFAULT_FILTER.to_string(),
+            }))
+            .await
+            .unwrap();
+
+        let stats = stats(&service, DEFAULT_FILTER).await.unwrap();
+        assert!((950..=1_050).contains(&stats.estimated_count));
+        assert!(stats.fill_ratio > 0.0 && stats.fill_ratio < 0.1);
+        assert!(stats.false_positive_rate < 0.01);
+        assert_eq!(stats.size_bytes, 119_814);
+    }
+
+    #[tokio::test]
+    async fn batch_insert() {
+        let service = service();
+        let items = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+
+        let res = service
+            .batch_insert(Request::new(BatchInsertRequest {
+                items: items.clone(),
+                filter_name: DEFAULT_FILTER.to_string(),
+            }))
+            .await
+            .unwrap();
+        assert_eq!(res.get_ref().num_items, 3);
+        for item in &items {
+            assert!(contains(&service, DEFAULT_FILTER, item).await);
+        }
+        assert!(!contains(&service, DEFAULT_FILTER, "yo").await);
+    }
+
+    #[tokio::test]
+    async fn named_filters_are_independent() {
+        let service = service();
+        insert(&service, "users", "alice").await;
+        insert(&service, "urls", "example.com").await;
+
+        assert!(contains(&service, "users", "alice").await);
+        assert!(!contains(&service, "users", "example.com").await);
+        assert!(contains(&service, "urls", "example.com").await);
+        assert!(!contains(&service, "urls", "alice").await);
+        assert!(!contains(&service, DEFAULT_FILTER, "alice").await);
+
+        service
+            .clear(Request::new(ClearRequest {
+                filter_name: "users".to_string(),
+            }))
+            .await
+            .unwrap();
+        assert!(!contains(&service, "users", "alice").await);
+        assert!(contains(&service, "urls", "example.com").await);
+    }
+
+    #[tokio::test]
+    async fn unknown_filter() {
+        let service = service();
+        assert!(!contains(&service, "nope", "hi").await);
+        assert_eq!(
+            stats(&service, "nope").await.unwrap_err().code(),
+            tonic::Code::NotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn watch_stats() {
+        use tokio_stream::StreamExt;
+
+        let service = Arc::new(service());
+        let mut stream = service
+            .watch_stats(Request::new(WatchStatsRequest {
+                filter_name: DEFAULT_FILTER.to_string(),
+                interval_ms: 10,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let inserter = {
+            let service = service.clone();
+            tokio::spawn(async move {
+                for i in 0..1_000 {
+                    insert(&service, DEFAULT_FILTER, &i.to_string()).await;
+                    if i % 100 == 0 {
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                    }
+                }
+            })
+        };
+
+        // Keep reading until the inserts have shown up in at least two increasing counts
+        let mut counts = vec![stream.next().await.unwrap().unwrap().estimated_count];
+        while counts.len() < 3 || counts[counts.len() - 1] < 950 {
+            let count = stream.next().await.unwrap().unwrap().estimated_count;
+            assert!(count >= counts[counts.len() - 1]);
+            if count > counts[counts.len() - 1] {
+                counts.push(count);
+            }
+        }
+        inserter.await.unwrap();
+        drop(stream);
+
+        assert!(counts.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[tokio::test]
+    async fn watch_stats_invalid() {
+        let service = service();
+        let watch = |filter_name: &str, interval_ms| {
+            service.watch_stats(Request::new(WatchStatsRequest {
+                filter_name: filter_name.to_string(),
+                interval_ms,
+            }))
+        };
+        assert_eq!(
+            watch("nope", 10).await.unwrap_err().code(),
+            tonic::Code::NotFound
+        );
+        assert_eq!(
+            watch(DEFAULT_FILTER, 0).await.unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_when_saturated() {
+        async fn batch_insert(service: &BloomdService, items: Vec<String>) -> Result<(), Status> {
+            service
+                .batch_insert(Request::new(BatchInsertRequest {
+                    items,
+                    filter_name: "small".to_string(),
+                }))
+                .await
+                .map(|_| ())
+        }
+
+        for reject in [false, true] {
+            let config = Config {
+                reject_when_saturated: reject,
+                ..Config::default()
+            };
+            let service = BloomdService::new(HashMap::new(), &config);
+            create_filter(&service, "small", 100, 0.01).await.unwrap();
+            // Comfortably past capacity, so that the filter is still saturated going by the
+            // estimate of its items once reloaded
+            batch_insert(&service, (0..110).map(|i| i.to_string()).collect())
+                .await
+                .unwrap();
+
+            // The filter is now past capacity, so further inserts are rejected if configured
+            let inserted = service
+                .insert(Request::new(InsertRequest {
+                    item: "hi".to_string(),
+                    filter_name: "small".to_string(),
+                }))
+                .await;
+            let batch_inserted = batch_insert(&service, vec!["yo".to_string()]).await;
+            if reject {
+                assert_eq!(inserted.unwrap_err().code(), tonic::Code::ResourceExhausted);
+                assert_eq!(
+                    batch_inserted.unwrap_err().code(),
+                    tonic::Code::ResourceExhausted
+                );
+                assert!(!contains(&service, "small", "hi").await);
+                assert!(!contains(&service, "small", "yo").await);
+            } else {
+                inserted.unwrap();
+                batch_inserted.unwrap();
+                assert!(contains(&service, "small", "hi").await);
+            }
+
+            // Other filters are unaffected
+            insert(&service, DEFAULT_FILTER, "hi").await;
+
+            // Nor does a restart forget the filter is saturated
+            let (_, filters) =
+                decode_filters(&encode_filters(config.hasher, &service.filters.read())).unwrap();
+            let restarted = BloomdService::new(filters, &config);
+            let inserted = batch_insert(&restarted, vec!["sup".to_string()]).await;
+            if reject {
+                assert_eq!(inserted.unwrap_err().code(), tonic::Code::ResourceExhausted);
+                assert!(!contains(&restarted, "small", "sup").await);
+            } else {
+                inserted.unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn max_item_bytes() {
+        let service = BloomdService::new(
+            HashMap::new(),
+            &Config {
+                max_item_bytes: Some(4),
+                ..Config::default()
+            },
+        );
+        insert(&service, DEFAULT_FILTER, "four").await;
+        assert!(contains(&service, DEFAULT_FILTER, "four").await);
+
+        let long = "fives".to_string();
+        let inserted = service
+            .insert(Request::new(InsertRequest {
+                item: long.clone(),
+                filter_name: "long".to_string(),
+            }))
+            .await;
+        assert_eq!(inserted.unwrap_err().code(), tonic::Code::InvalidArgument);
+        let batch_inserted = service
+            .batch_insert(Request::new(BatchInsertRequest {
+                items: vec!["hi".to_string(), long.clone()],
+                filter_name: DEFAULT_FILTER.to_string(),
+            }))
+            .await;
+        assert_eq!(
+            batch_inserted.unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+        let contained = service
+            .contains(Request::new(ContainsRequest {
+                item: long,
+                filter_name: DEFAULT_FILTER.to_string(),
+            }))
+            .await;
+        assert_eq!(contained.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        // Rejected requests leave the filters untouched
+        assert!(!contains(&service, DEFAULT_FILTER, "hi").await);
+        assert_eq!(
+            stats(&service, "long").await.unwrap_err().code(),
+            tonic::Code::NotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn create_filter_sizing() {
+        let service = service();
+        create_filter(&service, "small", 1_000, 0.01).await.unwrap();
+        insert(&service, "small", "hi").await;
+        assert!(contains(&service, "small", "hi").await);
+        assert_eq!(stats(&service, "small").await.unwrap().size_bytes, 1_199);
+
+        // Inserting into a missing filter creates it with the default sizing.
+        insert(&service, "lazy", "hi").await;
+        assert_eq!(stats(&service, "lazy").await.unwrap().size_bytes, 119_814);
+    }
+
+    #[tokio::test]
+    async fn create_filter_invalid() {
+        let service = service();
+        create_filter(&service, "a", 1_000, 0.01).await.unwrap();
+        assert_eq!(
+            create_filter(&service, "a", 1_000, 0.01)
+                .await
+                .unwrap_err()
+                .code(),
+            tonic::Code::AlreadyExists
+        );
+        // Rates that round to 0 as an `f32` are rejected rather than overflowing the sizing
+        for rate in [0.0, 1.0, -0.5, f64::NAN, 1e-50] {
+            assert_eq!(
+                create_filter(&service, "b", 1_000, rate)
+                    .await
+                    .unwrap_err()
+                    .code(),
+                tonic::Code::InvalidArgument
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn create_filter_too_large() {
+        let limited = BloomdService::new(
+            HashMap::new(),
+            &Config {
+                max_filter_bytes: 1_000,
+                ..Config::default()
+            },
+        );
+        let err = create_filter(&limited, "big", 1_000, 0.01)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        assert!(err.message().contains("1199 bytes"), "{}", err.message());
+        create_filter(&limited, "small", 800, 0.01).await.unwrap();
+
+        // A huge filter fails the request rather than the allocation
+        assert_eq!(
+            create_filter(&service(), "huge", 10_u64.pow(13), 0.01)
+                .await
+                .unwrap_err()
+                .code(),
+            tonic::Code::InvalidArgument
+        );
+        assert!(!limited.filters.read().contains_key("big"));
+    }
+
+    #[tokio::test]
+    async fn persist_across_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            persist_path: Some(dir.path().join("filters")),
+            ..Config::default()
+        };
+
+        // Nothing has been persisted yet, so only a fresh default filter exists.
+        let service = BloomdService::new(load_filters(&config).unwrap(), &config);
+        assert!(!contains(&service, DEFAULT_FILTER, "hi").await);
+        insert(&service, DEFAULT_FILTER, "hi").await;
+        create_filter(&service, "small", 1_000, 0.01).await.unwrap();
+        insert(&service, "small", "yo").await;
+        save_filters(
+            config.persist_path.as_deref().unwrap(),
+            config.hasher,
+            &service.filters.read(),
+        )
+        .unwrap();
+
+        let restarted = BloomdService::new(load_filters(&config).unwrap(), &config);
+        assert!(contains(&restarted, DEFAULT_FILTER, "hi").await);
+        assert!(!contains(&restarted, DEFAULT_FILTER, "yo").await);
+        assert!(contains(&restarted, "small", "yo").await);
+        assert_eq!(
+            encode_filters(config.hasher, &restarted.filters.read()),
+            encode_filters(config.hasher, &service.filters.read())
+        );
+    }
+
+    #[tokio::test]
+    async fn hashers() {
+        for hasher in [ItemHasher::Sip, ItemHasher::Fnv] {
+            let dir = tempfile::tempdir().unwrap();
+            let config = Config {
+                persist_path: Some(dir.path().join("filters")),
+                hasher,
+                ..Config::default()
+            };
+            let service = BloomdService::new(load_filters(&config).unwrap(), &config);
+            insert(&service, DEFAULT_FILTER, "hi").await;
+            create_filter(&service, "small", 1_000, 0.01).await.unwrap();
+            insert(&service, "small", "yo").await;
+            assert!(contains(&service, DEFAULT_FILTER, "hi").await);
+            assert!(!contains(&service, DEFAULT_FILTER, "yo").await);
+            assert!(contains(&service, "small", "yo").await);
+
+            save_filters(
+                config.persist_path.as_deref().unwrap(),
+                hasher,
+                &service.filters.read(),
+            )
+            .unwrap();
+            let restarted = BloomdService::new(load_filters(&config).unwrap(), &config);
+            assert!(contains(&restarted, DEFAULT_FILTER, "hi").await);
+            assert!(contains(&restarted, "small", "yo").await);
+
+            // Under the other hasher the items would map to other bits
+            let other = Config {
+                hasher: match hasher {
+                    ItemHasher::Sip => ItemHasher::Fnv,
+                    ItemHasher::Fnv => ItemHasher::Sip,
+                },
+                ..config
+            };
+            assert!(load_filters(&other).is_err());
+        }
+    }
+
+    #[test]
+    fn decode_filters_without_header() {
+        // Files from before the hasher was recorded were hashed with SipHash
+        let bloom = Filter::with_hasher(1_000, 0.01, ItemHasher::Sip);
+        bloom.insert("hi");
+        let mut filters = HashMap::new();
+        filters.insert("a".to_string(), bloom);
+        let bytes = encode_filters(ItemHasher::Sip, &filters);
+
+        let (hasher, decoded) = decode_filters(&bytes[PERSIST_MAGIC.len() + 1..]).unwrap();
+        assert_eq!(hasher, ItemHasher::Sip);
+        assert!(decoded["a"].contains("hi"));
+
+        let mut unknown = bytes.clone();
+        unknown[PERSIST_MAGIC.len()] = 0xff;
+        assert_eq!(
+            decode_filters(&unknown).unwrap_err(),
+            BloomError::UnsupportedHashScheme(0xff)
+        );
+    }
+
+    #[test]
+    fn decode_filters_truncated() {
+        let mut filters = HashMap::new();
+        filters.insert(
+            "a".to_string(),
+            Filter::with_hasher(1_000, 0.01, ItemHasher::Fnv),
+        );
+        let bytes = encode_filters(ItemHasher::Fnv, &filters);
+
+        let (hasher, decoded) = decode_filters(&bytes).unwrap();
+        assert_eq!(encode_filters(hasher, &decoded), bytes);
+        for len in [4, 10, bytes.len() - 1] {
+            assert_eq!(
+                decode_filters(&bytes[..len]).unwrap_err(),
+                BloomError::UnexpectedEof
+            );
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! End-to-end tests of the `bloom-cli` binary.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const BIN: &str = env!("CARGO_BIN_EXE_bloom-cli");
+
+#[test]
+fn build_and_query() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("words.bloom");
+
+    let mut build = Command::new(BIN)
+        .args([
+            "build",
+            "--num-elements",
+            "100",
+            "--fp-rate=0.001",
+            "--output",
+        ])
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    build
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"apple\nbanana\n\n  cherry \n")
+        .unwrap();
+    let output = build.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("Inserted 3 items"));
+
+    // The file holds the filter as the library serializes it
+    let bloom = bloom::BloomFilter::from_bytes(&std::fs::read(&path).unwrap()).unwrap();
+    assert!(bloom.contains("cherry"));
+    assert_eq!(bloom.capacity(), 100);
+
+    let output = Command::new(BIN)
+        .arg("query")
+        .arg(&path)
+        .args(["apple", "cherry"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"apple\ttrue\ncherry\ttrue\n");
+
+    let output = Command::new(BIN)
+        .arg("query")
+        .arg(&path)
+        .args(["banana", "durian"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"banana\ttrue\ndurian\tfalse\n");
+}
+
+#[test]
+fn invalid_arguments() {
+    let output = Command::new(BIN).arg("build").output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("missing --output"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("garbage.bloom");
+    std::fs::write(&path, b"not a filter").unwrap();
+    let output = Command::new(BIN)
+        .arg("query")
+        .arg(&path)
+        .arg("hi")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
@@ -0,0 +1,156 @@
+//! End-to-end tests of the `bloomd-server` binary, over gRPC.
+
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+use tonic::transport::Channel;
+
+mod bloomd {
+    tonic::include_proto!("bloomd");
+}
+
+use bloomd::bloomd_client::BloomdClient;
+use bloomd::{ContainsRequest, InsertRequest};
+
+const BIN: &str = env!("CARGO_BIN_EXE_bloomd-server");
+
+/// A running server, killed when dropped.
+struct Server {
+    child: Child,
+    addr: SocketAddr,
+}
+
+impl Server {
+    fn start(hasher: &str, persist_path: &Path) -> Server {
+        // Bind to an ephemeral port to find a free one for the server
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let child = Command::new(BIN)
+            .arg("--bind-addr")
+            .arg(addr.to_string())
+            .arg("--hasher")
+            .arg(hasher)
+            .arg("--persist-path")
+            .arg(persist_path)
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        Server { child, addr }
+    }
+
+    /// Connect to the server, waiting for it to start listening.
+    async fn connect(&mut self) -> BloomdClient<Channel> {
+        for _ in 0..100 {
+            if let Ok(client) = BloomdClient::connect(format!("http://{}", self.addr)).await {
+                return client;
+            }
+            assert!(self.child.try_wait().unwrap().is_none(), "server exited");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("server didn't start listening on {}", self.addr);
+    }
+
+    /// Shut the server down gracefully, so that it persists its filters.
+    ///
+    /// The server waits for clients to disconnect first, so this waits asynchronously to let
+    /// their connections close.
+    #[cfg(unix)]
+    async fn stop(mut self) {
+        let status = Command::new("kill")
+            .arg(self.child.id().to_string())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        loop {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                assert!(status.success());
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+async fn insert(client: &mut BloomdClient<Channel>, item: &str) {
+    client
+        .insert(InsertRequest {
+            item: item.to_string(),
+            filter_name: "words".to_string(),
+        })
+        .await
+        .unwrap();
+}
+
+async fn contains(client: &mut BloomdClient<Channel>, item: &str) -> bool {
+    client
+        .contains(ContainsRequest {
+            item: item.to_string(),
+            filter_name: "words".to_string(),
+        })
+        .await
+        .unwrap()
+        .get_ref()
+        .contains_item
+}
+
+#[tokio::test]
+async fn insert_and_contains_with_each_hasher() {
+    for hasher in ["sip", "fnv"] {
+        let dir = tempfile::tempdir().unwrap();
+        let mut server = Server::start(hasher, &dir.path().join("filters.bloomd"));
+        let mut client = server.connect().await;
+
+        assert!(!contains(&mut client, "hi").await);
+        insert(&mut client, "hi").await;
+        insert(&mut client, "yo").await;
+        assert!(contains(&mut client, "hi").await, "{}", hasher);
+        assert!(contains(&mut client, "yo").await, "{}", hasher);
+        assert!(!contains(&mut client, "sup").await, "{}", hasher);
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn persisted_under_each_hasher() {
+    for (hasher, other) in [("sip", "fnv"), ("fnv", "sip")] {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("filters.bloomd");
+        let mut server = Server::start(hasher, &path);
+        insert(&mut server.connect().await, "hi").await;
+        server.stop().await;
+
+        // Restarted with the same hasher, the server finds the item at the same bits
+        let mut server = Server::start(hasher, &path);
+        assert!(
+            contains(&mut server.connect().await, "hi").await,
+            "{}",
+            hasher
+        );
+        drop(server);
+
+        // Under the other hasher it would look elsewhere, so it refuses to start
+        let status = Command::new(BIN)
+            .args([
+                "--bind-addr",
+                "127.0.0.1:0",
+                "--hasher",
+                other,
+                "--persist-path",
+            ])
+            .arg(&path)
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(!status.success(), "{} loaded under {}", hasher, other);
+    }
+}